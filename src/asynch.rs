@@ -0,0 +1,233 @@
+//! Asynchronous driver based on the `embedded-hal-async` traits
+
+use embedded_hal_async::{delay, i2c};
+
+use crate::{
+    check_read_response, check_write_response,
+    protocol::{self, decode_device_info, decode_measurement, MAX_FRAME_LEN},
+    DeviceInfo, Error, RangePolicy, RawMeasurement, Register, Status, Timing, DEVICE_I2C_ADDR,
+    MAX_REGISTERS, REGISTER_MAP_SIZE,
+};
+
+/// Asynchronous sensor configuration
+///
+/// Same as [`Am2320`](crate::Am2320), but awaits the bus and the delays
+/// instead of blocking, which suits executors such as Embassy.
+pub struct Am2320Async<I2C, Delay> {
+    /// I2C master device to use to communicate with the sensor
+    device: I2C,
+    /// Delay device to be able to sleep in-between commands
+    delay: Delay,
+    /// I2C address of the sensor
+    address: u8,
+    /// Delays to wait in-between commands
+    timing: Timing,
+    /// What to do with measurements outside of the datasheet limits
+    range: RangePolicy,
+}
+
+impl<I2C, Delay, E> Am2320Async<I2C, Delay>
+where
+    I2C: i2c::I2c<Error = E>,
+    Delay: delay::DelayNs,
+{
+    /// Create an asynchronous AM2320 temperature sensor driver.
+    pub fn new(device: I2C, delay: Delay) -> Self {
+        Self::new_with_address(device, delay, DEVICE_I2C_ADDR)
+    }
+
+    /// Create an asynchronous AM2320 temperature sensor driver talking to `address`.
+    pub fn new_with_address(device: I2C, delay: Delay, address: u8) -> Self {
+        Self {
+            device,
+            delay,
+            address,
+            timing: Timing::default(),
+            range: RangePolicy::default(),
+        }
+    }
+
+    /// Uses the delays from `timing` in-between commands
+    pub fn with_timing(mut self, timing: Timing) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Applies `policy` to measurements outside of the datasheet limits
+    pub fn with_range_policy(mut self, policy: RangePolicy) -> Self {
+        self.range = policy;
+        self
+    }
+
+    /// Mutable access to the I2C device, to talk to other devices on the bus
+    pub fn device_mut(&mut self) -> &mut I2C {
+        &mut self.device
+    }
+
+    /// Mutable access to the delay device
+    pub fn delay_mut(&mut self) -> &mut Delay {
+        &mut self.delay
+    }
+
+    /// Destroys the driver and returns the I2C and delay devices
+    ///
+    /// To only lend the bus to the driver instead, create it from `&mut I2C`.
+    pub fn release(self) -> (I2C, Delay) {
+        (self.device, self.delay)
+    }
+
+    /// Reads one `Measurement` from the sensor
+    ///
+    /// Follows the same wake-up, command and read sequence as
+    /// [`Am2320::read`](crate::Am2320::read), yielding while waiting.
+    #[cfg(feature = "float")]
+    pub async fn read(&mut self) -> Result<crate::Measurement, Error<E>> {
+        self.read_raw().await.map(crate::Measurement::from)
+    }
+
+    /// Reads one `RawMeasurement` from the sensor
+    ///
+    /// Same as `read`, without any floating-point arithmetic.
+    pub async fn read_raw(&mut self) -> Result<RawMeasurement, Error<E>> {
+        let mut data = [0; 4];
+        self.read_registers(0x00, &mut data).await?;
+        self.range.apply(decode_measurement(&data))
+    }
+
+    /// Reads `buffer.len()` consecutive registers starting at `start`
+    ///
+    /// See [`Am2320::read_registers`](crate::Am2320::read_registers).
+    pub async fn read_registers(&mut self, start: u8, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        let command = protocol::encode_read_request(start, buffer.len())?;
+
+        let mut data = [0; MAX_REGISTERS + 4];
+        let data = &mut data[..buffer.len() + 4];
+        self.transfer(&command, data).await?;
+
+        buffer.copy_from_slice(check_read_response(data)?);
+        Ok(())
+    }
+
+    /// Writes `data` to consecutive registers starting at `start`
+    ///
+    /// See [`Am2320::write_registers`](crate::Am2320::write_registers).
+    pub async fn write_registers(&mut self, start: u8, data: &[u8]) -> Result<(), Error<E>> {
+        let mut command = [0; MAX_FRAME_LEN];
+        let command = protocol::encode_write_request(start, data, &mut command)?;
+
+        let mut response = [0; 5];
+        self.transfer(command, &mut response).await?;
+
+        check_write_response(&response, start, data.len())
+    }
+
+    /// Reads the model, version and device ID of the sensor
+    pub async fn identify(&mut self) -> Result<DeviceInfo, Error<E>> {
+        let mut data = [0; 7];
+        self.read_registers(Register::Model.address(), &mut data)
+            .await?;
+        Ok(decode_device_info(&data))
+    }
+
+    /// Reads the value of `register`, msb first
+    pub async fn read_register(&mut self, register: Register) -> Result<u32, Error<E>> {
+        let mut data = [0; 4];
+        let data = &mut data[..register.size()];
+        self.read_registers(register.address(), data).await?;
+        Ok(data
+            .iter()
+            .fold(0, |value, byte| value << 8 | u32::from(*byte)))
+    }
+
+    /// Reads the whole register map
+    ///
+    /// See [`Am2320::dump_all`](crate::Am2320::dump_all).
+    pub async fn dump_all(&mut self) -> Result<[u8; REGISTER_MAP_SIZE], Error<E>> {
+        let mut registers = [0; REGISTER_MAP_SIZE];
+        for (i, chunk) in registers.chunks_mut(MAX_REGISTERS).enumerate() {
+            self.read_registers((i * MAX_REGISTERS) as u8, chunk)
+                .await?;
+        }
+        Ok(registers)
+    }
+
+    /// Reads the status register (0x0F)
+    pub async fn status(&mut self) -> Result<Status, Error<E>> {
+        let mut data = [0; 1];
+        self.read_registers(Register::Status.address(), &mut data)
+            .await?;
+        Ok(Status::from(data[0]))
+    }
+
+    /// Reads user register 1 (0x10 and 0x11)
+    pub async fn user_register1(&mut self) -> Result<u16, Error<E>> {
+        let mut data = [0; 2];
+        self.read_registers(Register::UserReg1.address(), &mut data)
+            .await?;
+        Ok(u16::from_be_bytes(data))
+    }
+
+    /// Writes user register 1 (0x10 and 0x11)
+    pub async fn set_user_register1(&mut self, value: u16) -> Result<(), Error<E>> {
+        self.write_registers(Register::UserReg1.address(), &value.to_be_bytes())
+            .await
+    }
+
+    /// Reads user register 2 (0x12 and 0x13)
+    pub async fn user_register2(&mut self) -> Result<u16, Error<E>> {
+        let mut data = [0; 2];
+        self.read_registers(Register::UserReg2.address(), &mut data)
+            .await?;
+        Ok(u16::from_be_bytes(data))
+    }
+
+    /// Writes user register 2 (0x12 and 0x13)
+    pub async fn set_user_register2(&mut self, value: u16) -> Result<(), Error<E>> {
+        self.write_registers(Register::UserReg2.address(), &value.to_be_bytes())
+            .await
+    }
+
+    /// Wakes the sensor up, sends `command` and reads back its `response`
+    async fn transfer(&mut self, command: &[u8], response: &mut [u8]) -> Result<(), Error<E>> {
+        // The AM2320 won't ACK the wake-up write, see `Am2320::wake_up`.
+        let _ = self.device.write(self.address, &[0x00]).await;
+        self.delay.delay_us(self.timing.wake_us()).await;
+
+        self.device
+            .write(self.address, command)
+            .await
+            .map_err(Error::WriteError)?;
+        self.delay.delay_us(self.timing.conversion_us()).await;
+
+        self.device
+            .read(self.address, response)
+            .await
+            .map_err(Error::ReadError)
+    }
+}
+
+#[test]
+fn test_read_async() {
+    use crate::mock;
+
+    let mut am2320 = Am2320Async::new(
+        mock::I2c::new(&[&[0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x50, 0x05]]),
+        mock::Delay,
+    );
+    let measurement = mock::block_on(am2320.read_raw()).unwrap();
+    assert_eq!(measurement.humidity_permille, 566);
+    assert_eq!(measurement.temperature_decicelsius, 219);
+}
+
+#[test]
+fn test_release_async() {
+    use crate::mock;
+
+    let response: &[u8] = &[0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x50, 0x05];
+    let mut bus = mock::I2c::new(&[response]);
+    let mut am2320 = Am2320Async::new(&mut bus, mock::Delay);
+    mock::block_on(am2320.read_raw()).unwrap();
+    assert_eq!(am2320.device_mut().writes.len(), 2);
+    let (bus, _) = am2320.release();
+    assert_eq!(bus.writes[1], [0x03, 0x00, 0x04]);
+}
@@ -0,0 +1,181 @@
+//! Command-line tool to query an AM2320 sensor on a Linux I2C bus
+
+use std::{
+    error::Error,
+    num::ParseIntError,
+    path::PathBuf,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use am2320::{Am2320, Measurement, DEVICE_I2C_ADDR, MIN_SAMPLING_INTERVAL_US};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::json;
+
+/// Query an AM2320 temperature and humidity sensor
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// I2C bus the sensor is connected to
+    #[arg(short, long, default_value = "/dev/i2c-1")]
+    bus: PathBuf,
+    /// I2C address of the sensor
+    #[arg(short, long, default_value_t = DEVICE_I2C_ADDR, value_parser = parse_u8)]
+    address: u8,
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Read the temperature and humidity once
+    Read,
+    /// Read the model, version and device ID
+    Identify,
+    /// Read the whole register map
+    DumpRegisters,
+    /// Write one of the two user registers
+    WriteUserReg {
+        /// User register to write, 1 or 2
+        #[arg(value_parser = clap::value_parser!(u8).range(1..=2))]
+        register: u8,
+        /// Value to write, decimal or hexadecimal with a `0x` prefix
+        #[arg(value_parser = parse_u16)]
+        value: u16,
+    },
+    /// Read the temperature and humidity periodically
+    Watch {
+        /// Seconds to wait in-between measurements, at least 2
+        #[arg(short, long, default_value_t = 2.0)]
+        interval: f64,
+        /// Stop after this many measurements
+        #[arg(short, long)]
+        count: Option<u64>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Human-readable text
+    Text,
+    /// One JSON object per line
+    Json,
+    /// Comma-separated values with a header line
+    Csv,
+}
+
+fn parse_u8(s: &str) -> Result<u8, ParseIntError> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+}
+
+fn parse_u16(s: &str) -> Result<u16, ParseIntError> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    let mut am2320 = Am2320::open_with_address(&cli.bus, cli.address)?;
+
+    match cli.command {
+        Command::Read => {
+            let measurement = am2320.read()?;
+            print_header(cli.format, "temperature,humidity");
+            print_measurement(cli.format, None, &measurement);
+        }
+        Command::Identify => {
+            let info = am2320.identify()?;
+            match cli.format {
+                Format::Text => println!(
+                    "model: {:#06x}\nversion: {:#04x}\nid: {:#010x}",
+                    info.model, info.version, info.id
+                ),
+                Format::Json => println!("{}", serde_json::to_string(&info)?),
+                Format::Csv => println!(
+                    "model,version,id\n{},{},{}",
+                    info.model, info.version, info.id
+                ),
+            }
+        }
+        Command::DumpRegisters => {
+            let registers = am2320.dump_all()?;
+            match cli.format {
+                Format::Text => {
+                    for (start, row) in registers.chunks(8).enumerate() {
+                        let bytes: Vec<_> =
+                            row.iter().map(|byte| format!("{:02x}", byte)).collect();
+                        println!("{:#04x}: {}", start * 8, bytes.join(" "));
+                    }
+                }
+                Format::Json => println!("{}", json!({ "registers": registers.to_vec() })),
+                Format::Csv => {
+                    println!("register,value");
+                    for (register, value) in registers.iter().enumerate() {
+                        println!("{},{}", register, value);
+                    }
+                }
+            }
+        }
+        Command::WriteUserReg { register, value } => match register {
+            1 => am2320.set_user_register1(value)?,
+            _ => am2320.set_user_register2(value)?,
+        },
+        Command::Watch { interval, count } => {
+            let min_interval = MIN_SAMPLING_INTERVAL_US as f64 / 1e6;
+            if interval.is_nan() || interval < min_interval {
+                return Err(format!("the interval must be at least {} s", min_interval).into());
+            }
+            let interval = Duration::from_secs_f64(interval);
+
+            print_header(cli.format, "timestamp,temperature,humidity");
+            let mut taken = 0;
+            while count.is_none_or(|count| taken < count) {
+                let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+                // A failed measurement doesn't stop the watch, the next one
+                // will likely succeed.
+                match am2320.read() {
+                    Ok(measurement) => print_measurement(cli.format, Some(timestamp), &measurement),
+                    Err(e) => eprintln!("{}", e),
+                }
+                taken += 1;
+                if count != Some(taken) {
+                    thread::sleep(interval);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_header(format: Format, header: &str) {
+    if format == Format::Csv {
+        println!("{}", header);
+    }
+}
+
+fn print_measurement(format: Format, timestamp: Option<u64>, measurement: &Measurement) {
+    let temperature = measurement.temperature.celsius();
+    let humidity = measurement.humidity.percent();
+    match (format, timestamp) {
+        (Format::Text, Some(timestamp)) => {
+            println!("{}: {:.1} °C, {:.1} %", timestamp, temperature, humidity)
+        }
+        (Format::Text, None) => println!("{:.1} °C, {:.1} %", temperature, humidity),
+        (Format::Json, Some(timestamp)) => println!(
+            "{}",
+            json!({ "timestamp": timestamp, "temperature": temperature, "humidity": humidity })
+        ),
+        (Format::Json, None) => println!("{}", json!(measurement)),
+        (Format::Csv, Some(timestamp)) => println!("{},{},{}", timestamp, temperature, humidity),
+        (Format::Csv, None) => println!("{},{}", temperature, humidity),
+    }
+}
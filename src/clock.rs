@@ -0,0 +1,63 @@
+//! Time source used to enforce the minimum sampling interval
+
+/// Minimum time between two measurements according to the datasheet
+///
+/// Polling the sensor more often makes it heat up, which skews the humidity.
+pub const MIN_SAMPLING_INTERVAL_US: u64 = 2_000_000;
+
+/// Monotonic time source
+pub trait Clock {
+    /// Returns the current time in microseconds, from an arbitrary origin
+    fn now_us(&mut self) -> u64;
+}
+
+/// Placeholder for drivers created without a time source
+///
+/// The sampling interval isn't enforced until a real clock is provided with
+/// [`Am2320::with_clock`](crate::Am2320::with_clock). `NoClock` isn't a
+/// `Clock`, so the split-phase measurements and `Sampler`, which can't work
+/// without one, aren't available either:
+///
+/// ```compile_fail
+/// # fn measure<I2C: embedded_hal::i2c::I2c, Delay: embedded_hal::delay::DelayNs>(
+/// #     am2320: &mut am2320::Am2320<I2C, Delay>,
+/// # ) {
+/// let _ = am2320.finish_measurement();
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoClock;
+
+/// Time source of a driver, either a `Clock` or `NoClock`
+///
+/// This trait is sealed, it is only implemented by this crate.
+pub trait MaybeClock {
+    /// Returns the current time in microseconds, `None` without a clock
+    fn try_now_us(&mut self) -> Option<u64>;
+}
+
+impl<C: Clock> MaybeClock for C {
+    fn try_now_us(&mut self) -> Option<u64> {
+        Some(self.now_us())
+    }
+}
+
+impl MaybeClock for NoClock {
+    fn try_now_us(&mut self) -> Option<u64> {
+        None
+    }
+}
+
+/// What to do when a measurement is requested less than
+/// `MIN_SAMPLING_INTERVAL_US` after the previous one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SamplingPolicy {
+    /// Return the previous measurement again
+    Cached,
+    /// Fail with `Error::TooSoon`
+    Reject,
+    /// Block until the sensor can be polled again
+    Wait,
+}
@@ -0,0 +1,169 @@
+//! Errors returned by the driver
+
+use core::fmt;
+
+use embedded_hal::i2c;
+
+use crate::{protocol::FrameError, RawMeasurement};
+
+/// Describes potential errors
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// Something went wrong while writing to the sensor
+    WriteError(E),
+    /// Something went wrong while reading from the sensor
+    ReadError(E),
+    /// The sensor replied with a Modbus exception
+    ModbusException(ModbusException),
+    /// The CRC computed over the response doesn't match the one sent by the sensor
+    CrcMismatch {
+        /// CRC computed over the received data
+        expected: u16,
+        /// CRC sent by the sensor
+        actual: u16,
+    },
+    /// The response doesn't echo the function code, address or length of the command
+    UnexpectedHeader,
+    /// The sensor returned a measurement outside of the datasheet limits
+    OutOfRange(RawMeasurement),
+    /// The sensor was polled before the minimum sampling interval elapsed
+    TooSoon {
+        /// Time left before the sensor can be polled again, in milliseconds
+        wait_ms: u32,
+    },
+    /// The requested register range is empty, longer than `MAX_REGISTERS` or
+    /// goes past the end of the register map
+    InvalidRegisterRange,
+}
+
+impl<E> Error<E> {
+    /// Returns `true` for errors that may go away when retrying the transfer
+    ///
+    /// These are bus errors and corrupted responses, as opposed to commands
+    /// refused by the sensor or rejected by the driver.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Error::WriteError(_)
+                | Error::ReadError(_)
+                | Error::CrcMismatch { .. }
+                | Error::UnexpectedHeader
+        )
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::WriteError(e) => write!(f, "failed to write to the sensor: {:?}", e),
+            Error::ReadError(e) => write!(f, "failed to read from the sensor: {:?}", e),
+            Error::ModbusException(e) => write!(f, "the sensor refused the command: {}", e),
+            Error::CrcMismatch { expected, actual } => write!(
+                f,
+                "CRC mismatch, expected {:#06x} but the sensor sent {:#06x}",
+                expected, actual
+            ),
+            Error::UnexpectedHeader => f.write_str("unexpected response header"),
+            Error::OutOfRange(measurement) => {
+                write!(f, "measurement out of range: {}", measurement)
+            }
+            Error::TooSoon { wait_ms } => {
+                write!(f, "polled too soon, wait another {} ms", wait_ms)
+            }
+            Error::InvalidRegisterRange => f.write_str("invalid register range"),
+        }
+    }
+}
+
+impl<E: fmt::Debug> core::error::Error for Error<E> {}
+
+impl<E> From<FrameError> for Error<E> {
+    fn from(error: FrameError) -> Self {
+        match error {
+            FrameError::InvalidRegisterRange => Error::InvalidRegisterRange,
+            FrameError::Truncated | FrameError::UnexpectedHeader => Error::UnexpectedHeader,
+            FrameError::CrcMismatch { expected, actual } => Error::CrcMismatch { expected, actual },
+        }
+    }
+}
+
+impl<E: i2c::Error> i2c::Error for Error<E> {
+    /// Returns the kind of the underlying bus error, or `Other` for errors
+    /// reported by the sensor itself
+    fn kind(&self) -> i2c::ErrorKind {
+        match self {
+            Error::WriteError(e) | Error::ReadError(e) => e.kind(),
+            _ => i2c::ErrorKind::Other,
+        }
+    }
+}
+
+/// Exception codes sent back by the sensor when it refuses a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ModbusException {
+    /// The function code isn't supported (0x80)
+    UnsupportedFunction,
+    /// The register address is out of the register map (0x81)
+    IllegalAddress,
+    /// The data to write is invalid (0x82)
+    WriteDataError,
+    /// The CRC of the command didn't match (0x83)
+    CrcError,
+    /// The registers are read-only (0x84)
+    WriteDisabled,
+    /// Any other exception code
+    Unknown(u8),
+}
+
+impl From<u8> for ModbusException {
+    fn from(code: u8) -> Self {
+        match code {
+            0x80 => ModbusException::UnsupportedFunction,
+            0x81 => ModbusException::IllegalAddress,
+            0x82 => ModbusException::WriteDataError,
+            0x83 => ModbusException::CrcError,
+            0x84 => ModbusException::WriteDisabled,
+            code => ModbusException::Unknown(code),
+        }
+    }
+}
+
+impl fmt::Display for ModbusException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModbusException::UnsupportedFunction => f.write_str("unsupported function"),
+            ModbusException::IllegalAddress => f.write_str("illegal address"),
+            ModbusException::WriteDataError => f.write_str("write data error"),
+            ModbusException::CrcError => f.write_str("CRC error"),
+            ModbusException::WriteDisabled => f.write_str("write disabled"),
+            ModbusException::Unknown(code) => write!(f, "unknown exception {:#04x}", code),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde() {
+    let errors: [Error<()>; 2] = [
+        Error::CrcMismatch {
+            expected: 0xa0a1,
+            actual: 0x0000,
+        },
+        Error::ModbusException(ModbusException::Unknown(0x85)),
+    ];
+    let json = serde_json::to_string(&errors).unwrap();
+    assert_eq!(
+        json,
+        r#"[{"CrcMismatch":{"expected":41121,"actual":0}},{"ModbusException":{"Unknown":133}}]"#
+    );
+    assert_eq!(
+        serde_json::from_str::<[Error<()>; 2]>(&json).unwrap(),
+        errors
+    );
+}
@@ -0,0 +1,66 @@
+//! Status register and health report
+
+use crate::{DeviceInfo, RawMeasurement};
+
+/// Content of the status register (0x0F)
+///
+/// The datasheet reserves every bit of this register and genuine sensors
+/// report 0, so any bit set points at a faulty unit or at a clone using the
+/// register differently. No bit has a documented meaning, hence the raw
+/// value and `is_nominal` are all there is to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Status(u8);
+
+impl Status {
+    /// Creates a status from the raw register value
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    /// Raw register value
+    pub const fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns `true` if no bit is set, as on a healthy sensor
+    pub const fn is_nominal(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl From<u8> for Status {
+    fn from(bits: u8) -> Self {
+        Self(bits)
+    }
+}
+
+/// Result of [`Am2320::health_check`](crate::Am2320::health_check)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HealthReport {
+    /// Model, version and device ID
+    pub info: DeviceInfo,
+    /// Status register
+    pub status: Status,
+    /// Measurement as sent by the sensor, whatever the `RangePolicy`
+    pub measurement: RawMeasurement,
+}
+
+impl HealthReport {
+    /// Returns `true` if the sensor identifies itself, reports a nominal
+    /// status and measures values within the datasheet limits
+    pub fn is_healthy(&self) -> bool {
+        !self.info.is_blank() && self.status.is_nominal() && self.measurement.is_in_range()
+    }
+}
+
+#[test]
+fn test_status() {
+    let status = Status::from(0b1000_0001);
+    assert_eq!(status.bits(), 0x81);
+    assert!(!status.is_nominal());
+    assert!(Status::default().is_nominal());
+}
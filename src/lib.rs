@@ -6,53 +6,117 @@
 #![no_std]
 #![deny(warnings, missing_docs)]
 
+#[cfg(feature = "std")]
+extern crate std;
+
 use embedded_hal::{delay, i2c};
 
-const DEVICE_I2C_ADDR: u8 = 0x5c;
+use nonblocking::Pending;
+use protocol::{
+    decode_device_info, decode_measurement, Response, MAX_FRAME_LEN, READ_REGISTERS,
+    WRITE_REGISTERS,
+};
+
+#[cfg(feature = "async")]
+mod asynch;
+mod clock;
+mod error;
+mod health;
+#[cfg(feature = "linux")]
+mod linux;
+mod measurement;
+#[cfg(test)]
+mod mock;
+mod mux;
+mod nonblocking;
+pub mod protocol;
+#[cfg(feature = "libm")]
+mod psychrometrics;
+mod register;
+mod retry;
+mod sampler;
+#[cfg(any(feature = "embedded-hal-bus", feature = "embassy"))]
+mod shared;
+#[cfg(feature = "sim")]
+pub mod sim;
+pub mod single_bus;
+mod timing;
+#[cfg(feature = "float")]
+mod units;
+
+#[cfg(feature = "async")]
+pub use asynch::Am2320Async;
+use clock::MaybeClock;
+pub use clock::{Clock, NoClock, SamplingPolicy, MIN_SAMPLING_INTERVAL_US};
+pub use error::{Error, ModbusException};
+pub use health::{HealthReport, Status};
+#[cfg(feature = "float")]
+pub use measurement::Measurement;
+pub use measurement::{RangePolicy, RawMeasurement};
+pub use mux::{Tca9548a, Tca9548aChannel};
+pub use nonblocking::NoDelay;
+pub use register::Register;
+pub use retry::RetryPolicy;
+pub use sampler::{Filter, Sampler};
+pub use timing::Timing;
+#[cfg(feature = "float")]
+pub use units::{RelativeHumidity, Temperature};
 
-/// Describes potential errors
-#[derive(Debug)]
-pub enum Error {
-    /// Something went wrong while writing to the sensor
-    WriteError,
-    /// Something went wrong while reading from the sensor
-    ReadError,
-    /// The sensor returned data that is out of spec
-    SensorError,
+/// Default I2C address of the sensor
+pub const DEVICE_I2C_ADDR: u8 = 0x5c;
+
+/// Maximum number of registers the sensor accepts in a single command
+pub const MAX_REGISTERS: usize = 10;
+/// Size of the register map, addresses go from 0x00 to 0x1F
+pub const REGISTER_MAP_SIZE: usize = 0x20;
+
+/// Identification of the sensor, read from registers 0x08 to 0x0E
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DeviceInfo {
+    /// Model number
+    pub model: u16,
+    /// Version number
+    pub version: u8,
+    /// 32-bit device ID
+    pub id: u32,
 }
 
-/// Representation of a measurement from the sensor
-#[derive(Debug)]
-pub struct Measurement {
-    /// Temperature in degrees celsius (°C)
-    pub temperature: f32,
-    /// Humidity in percent (%)
-    pub humidity: f32,
+impl DeviceInfo {
+    /// Returns `true` if all the identification registers read as zero
+    ///
+    /// Clones of the AM2320 commonly leave these registers blank, which
+    /// makes them easy to tell apart from genuine parts.
+    pub fn is_blank(&self) -> bool {
+        self.model == 0 && self.version == 0 && self.id == 0
+    }
 }
 
 /// Sensor configuration
-pub struct Am2320<I2C, Delay> {
+pub struct Am2320<I2C, Delay, C = NoClock> {
     /// I2C master device to use to communicate with the sensor
     device: I2C,
     /// Delay device to be able to sleep in-between commands
     delay: Delay,
-}
-
-#[inline(always)]
-fn crc16(data: &[u8]) -> u16 {
-    let mut crc: u16 = 0xFFFF;
-    for e in data.iter() {
-        crc ^= u16::from(*e);
-        for _ in 0..8 {
-            if crc & 0x0001 == 0x0001 {
-                crc >>= 1;
-                crc ^= 0xA001;
-            } else {
-                crc >>= 1;
-            }
-        }
-    }
-    crc
+    /// I2C address of the sensor
+    address: u8,
+    /// Delays to wait in-between commands
+    timing: Timing,
+    /// What to do with measurements outside of the datasheet limits
+    range: RangePolicy,
+    /// Time source to track the sampling interval
+    clock: C,
+    /// Policy applied when polling too often, `None` when there's no clock
+    sampling: Option<SamplingPolicy>,
+    /// Time and value of the last successful measurement
+    last_sample: Option<(u64, RawMeasurement)>,
+    /// Step of the measurement started with `start_measurement`
+    pending: Option<Pending>,
+    /// How transfers failing with a transient error are retried
+    retry: RetryPolicy,
+    /// Number of attempts made by the last transfer
+    last_attempts: u8,
 }
 
 impl<I2C, Delay, E> Am2320<I2C, Delay>
@@ -64,10 +128,10 @@ where
     ///
     /// Example with `rppal`:
     ///
-    /// ```!ignore
+    /// ```ignore
     /// use am2320::*;
     /// use rppal::{hal::Delay, i2c::I2c};
-    /// fn main() -> Result<(), Error> {
+    /// fn main() -> Result<(), Error<rppal::i2c::Error>> {
     ///     let device = I2c::new().expect("could not initialize I2c on your RPi");
     ///     let delay = Delay::new();
     ///
@@ -78,9 +142,156 @@ where
     /// }
     /// ```
     pub fn new(device: I2C, delay: Delay) -> Self {
-        Self { device, delay }
+        Self::new_with_address(device, delay, DEVICE_I2C_ADDR)
+    }
+
+    /// Create a AM2320 temperature sensor driver talking to `address`.
+    ///
+    /// The sensor always answers on `DEVICE_I2C_ADDR`, but an address
+    /// translator in front of it lets several of them share a bus. To put
+    /// several sensors behind a TCA9548A multiplexer instead, see
+    /// [`Tca9548a`].
+    pub fn new_with_address(device: I2C, delay: Delay, address: u8) -> Self {
+        Self::from_parts(device, delay, NoClock, address)
+    }
+}
+
+impl<I2C, Delay, C> Am2320<I2C, Delay, C> {
+    /// Creates a driver with the default configuration
+    fn from_parts(device: I2C, delay: Delay, clock: C, address: u8) -> Self {
+        Self {
+            device,
+            delay,
+            address,
+            timing: Timing::default(),
+            range: RangePolicy::default(),
+            clock,
+            sampling: None,
+            last_sample: None,
+            pending: None,
+            retry: RetryPolicy::none(),
+            last_attempts: 0,
+        }
+    }
+
+    /// Enforces the minimum sampling interval using `clock`
+    ///
+    /// `read` then applies `policy` whenever it is called less than
+    /// `MIN_SAMPLING_INTERVAL_US` after the last successful measurement.
+    pub fn with_clock<C2: Clock>(
+        self,
+        clock: C2,
+        policy: SamplingPolicy,
+    ) -> Am2320<I2C, Delay, C2> {
+        Am2320 {
+            device: self.device,
+            delay: self.delay,
+            address: self.address,
+            timing: self.timing,
+            range: self.range,
+            clock,
+            sampling: Some(policy),
+            last_sample: None,
+            pending: None,
+            retry: self.retry,
+            last_attempts: self.last_attempts,
+        }
+    }
+
+    /// Enforces the minimum sampling interval with the driver's own clock
+    ///
+    /// Same as `with_clock` for drivers that already have one, such as the
+    /// ones created with `new_nonblocking`.
+    pub fn with_sampling_policy(mut self, policy: SamplingPolicy) -> Self
+    where
+        C: Clock,
+    {
+        self.sampling = Some(policy);
+        self
+    }
+
+    /// Uses the delays from `timing` in-between commands
+    pub fn with_timing(mut self, timing: Timing) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Applies `policy` to measurements outside of the datasheet limits
+    pub fn with_range_policy(mut self, policy: RangePolicy) -> Self {
+        self.range = policy;
+        self
+    }
+
+    /// Retries transfers failing with a transient error according to `policy`
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Returns the number of attempts made by the last transfer
+    ///
+    /// This is 1 when the transfer succeeded right away, more when it had to
+    /// be retried and 0 when no transfer was made at all, for instance when
+    /// `read` returned a cached measurement. After `health_check`, it counts
+    /// the attempts of both of its reads.
+    pub fn last_attempts(&self) -> u8 {
+        self.last_attempts
+    }
+
+    /// Mutable access to the I2C device, to talk to other devices on the bus
+    ///
+    /// The sensor goes back to sleep on its own, so the bus can be used
+    /// freely in-between measurements.
+    pub fn device_mut(&mut self) -> &mut I2C {
+        &mut self.device
+    }
+
+    /// Mutable access to the delay device
+    pub fn delay_mut(&mut self) -> &mut Delay {
+        &mut self.delay
+    }
+
+    /// Destroys the driver and returns the I2C and delay devices
+    ///
+    /// To only lend the bus to the driver instead, create it from `&mut I2C`,
+    /// which implements `I2c` as well.
+    pub fn release(self) -> (I2C, Delay) {
+        (self.device, self.delay)
+    }
+
+    /// Wakes the sensor up, without waiting for it
+    fn wake_up(&mut self)
+    where
+        I2C: i2c::I2c,
+    {
+        // We need to wake up the AM2320, since it goes to sleep in order not
+        // to warm up and affect the humidity sensor. This write will fail as
+        // the AM2320 won't ACK this write.
+        let _ = self.device.write(self.address, &[0x00]);
     }
 
+    /// Returns the sampling policy, the time left before the sensor can be
+    /// polled again and the last measurement, `None` when it can be polled
+    /// right away
+    fn sampling_wait(&mut self) -> Option<(SamplingPolicy, u64, RawMeasurement)>
+    where
+        C: MaybeClock,
+    {
+        let (policy, (at, measurement)) = (self.sampling?, self.last_sample?);
+        let elapsed = self.clock.try_now_us()?.saturating_sub(at);
+        if elapsed >= MIN_SAMPLING_INTERVAL_US {
+            return None;
+        }
+        Some((policy, MIN_SAMPLING_INTERVAL_US - elapsed, measurement))
+    }
+}
+
+impl<I2C, Delay, C, E> Am2320<I2C, Delay, C>
+where
+    I2C: i2c::I2c<Error = E>,
+    Delay: delay::DelayNs,
+    C: MaybeClock,
+{
     /// Reads one `Measurement` from the sensor
     ///
     /// The operation is blocking, and should take ~3 ms according the spec.
@@ -88,62 +299,514 @@ where
     /// Then it'll wait a while before sending data in-order for the measurement
     /// to be more accurate.
     ///
-    pub fn read(&mut self) -> Result<Measurement, Error> {
-        // We need to wake up the AM2320, since it goes to sleep in order not
-        // to warm up and affect the humidity sensor. This write will fail as
-        // the AM2320 won't ACK this write.
-        let _ = self.device.write(DEVICE_I2C_ADDR, &[0x00]);
+    /// When a clock was provided with `with_clock`, polling faster than
+    /// `MIN_SAMPLING_INTERVAL_US` is handled according to the `SamplingPolicy`.
+    #[cfg(feature = "float")]
+    pub fn read(&mut self) -> Result<Measurement, Error<E>> {
+        self.read_raw().map(Measurement::from)
+    }
+
+    /// Reads one measurement from the sensor as `uom` quantities
+    #[cfg(feature = "uom")]
+    pub fn read_quantities(
+        &mut self,
+    ) -> Result<(uom::si::f32::ThermodynamicTemperature, uom::si::f32::Ratio), Error<E>> {
+        let measurement = self.read()?;
+        Ok((measurement.temperature.into(), measurement.humidity.into()))
+    }
+
+    /// Reads one `RawMeasurement` from the sensor
+    ///
+    /// Same as `read`, without any floating-point arithmetic.
+    pub fn read_raw(&mut self) -> Result<RawMeasurement, Error<E>> {
+        self.last_attempts = 0;
+        if let Some((policy, wait_us, measurement)) = self.sampling_wait() {
+            match policy {
+                SamplingPolicy::Cached => return Ok(measurement),
+                SamplingPolicy::Reject => return Err(too_soon(wait_us)),
+                SamplingPolicy::Wait => self.delay.delay_us(wait_us as u32),
+            }
+        }
+
+        let mut data = [0; 4];
+        self.read_registers(0x00, &mut data)?;
+        let measurement = self.range.apply(decode_measurement(&data))?;
+
+        if self.sampling.is_some() {
+            self.last_sample = self.clock.try_now_us().map(|now| (now, measurement));
+        }
+        Ok(measurement)
+    }
+
+    /// Reads `buffer.len()` consecutive registers starting at `start`
+    ///
+    /// Issues the Modbus function code 0x03 and fills `buffer` with the raw
+    /// register values once the response header and CRC have been checked.
+    /// At most `MAX_REGISTERS` registers can be read at once.
+    pub fn read_registers(&mut self, start: u8, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        let command = protocol::encode_read_request(start, buffer.len())?;
+
+        let mut data = [0; MAX_REGISTERS + 4];
+        let data = &mut data[..buffer.len() + 4];
+        self.retrying(|am2320| {
+            am2320.transfer(&command, data)?;
+            buffer.copy_from_slice(check_read_response(data)?);
+            Ok(())
+        })
+    }
+
+    /// Writes `data` to consecutive registers starting at `start`
+    ///
+    /// Issues the Modbus function code 0x10 and checks the acknowledgement
+    /// sent back by the sensor. Only the user registers (0x10 to 0x13) are
+    /// writable, the sensor rejects writes anywhere else.
+    pub fn write_registers(&mut self, start: u8, data: &[u8]) -> Result<(), Error<E>> {
+        let mut command = [0; MAX_FRAME_LEN];
+        let command = protocol::encode_write_request(start, data, &mut command)?;
+
+        let mut response = [0; 5];
+        self.retrying(|am2320| {
+            am2320.transfer(command, &mut response)?;
+            check_write_response(&response, start, data.len())
+        })
+    }
+
+    /// Reads the model, version and device ID of the sensor
+    pub fn identify(&mut self) -> Result<DeviceInfo, Error<E>> {
+        let mut data = [0; 7];
+        self.read_registers(Register::Model.address(), &mut data)?;
+        Ok(decode_device_info(&data))
+    }
+
+    /// Reads the value of `register`, msb first
+    pub fn read_register(&mut self, register: Register) -> Result<u32, Error<E>> {
+        let mut data = [0; 4];
+        let data = &mut data[..register.size()];
+        self.read_registers(register.address(), data)?;
+        Ok(data
+            .iter()
+            .fold(0, |value, byte| value << 8 | u32::from(*byte)))
+    }
+
+    /// Reads the whole register map
+    ///
+    /// The map is read in chunks of at most `MAX_REGISTERS` registers, each
+    /// one waking the sensor up again.
+    pub fn dump_all(&mut self) -> Result<[u8; REGISTER_MAP_SIZE], Error<E>> {
+        let mut registers = [0; REGISTER_MAP_SIZE];
+        for (i, chunk) in registers.chunks_mut(MAX_REGISTERS).enumerate() {
+            self.read_registers((i * MAX_REGISTERS) as u8, chunk)?;
+        }
+        Ok(registers)
+    }
+
+    /// Reads the status register (0x0F)
+    pub fn status(&mut self) -> Result<Status, Error<E>> {
+        let mut data = [0; 1];
+        self.read_registers(Register::Status.address(), &mut data)?;
+        Ok(Status::from(data[0]))
+    }
+
+    /// Reads the identification, the status and a measurement
+    ///
+    /// The measurement is read right after registers 0x08 to 0x0F, while the
+    /// sensor is still awake, so the whole check only costs one wake-up.
+    /// Neither the range nor the sampling policy is applied to the
+    /// measurement, see `HealthReport::is_healthy`.
+    ///
+    /// `last_attempts` then counts the attempts of both reads.
+    pub fn health_check(&mut self) -> Result<HealthReport, Error<E>> {
+        let mut data = [0; 8];
+        self.read_registers(Register::Model.address(), &mut data)?;
+        let mut info = [0; 7];
+        info.copy_from_slice(&data[..7]);
+        let info_attempts = self.last_attempts;
+
+        let command = protocol::encode_read_request(0x00, 4)?;
+        let mut response = [0; 8];
+        let mut first_attempt = true;
+        let measurement = self.retrying(|am2320| {
+            // Only retries have to wake the sensor up again
+            if core::mem::take(&mut first_attempt) {
+                am2320.exchange(&command, &mut response)?;
+            } else {
+                am2320.transfer(&command, &mut response)?;
+            }
+            let mut registers = [0; 4];
+            registers.copy_from_slice(check_read_response(&response)?);
+            Ok(decode_measurement(&registers))
+        });
+        self.last_attempts = self.last_attempts.saturating_add(info_attempts);
+        let measurement = measurement?;
+
+        Ok(HealthReport {
+            info: decode_device_info(&info),
+            status: Status::from(data[7]),
+            measurement,
+        })
+    }
+
+    /// Reads user register 1 (0x10 and 0x11)
+    pub fn user_register1(&mut self) -> Result<u16, Error<E>> {
+        let mut data = [0; 2];
+        self.read_registers(Register::UserReg1.address(), &mut data)?;
+        Ok(u16::from_be_bytes(data))
+    }
+
+    /// Writes user register 1 (0x10 and 0x11)
+    pub fn set_user_register1(&mut self, value: u16) -> Result<(), Error<E>> {
+        self.write_registers(Register::UserReg1.address(), &value.to_be_bytes())
+    }
+
+    /// Reads user register 2 (0x12 and 0x13)
+    pub fn user_register2(&mut self) -> Result<u16, Error<E>> {
+        let mut data = [0; 2];
+        self.read_registers(Register::UserReg2.address(), &mut data)?;
+        Ok(u16::from_be_bytes(data))
+    }
+
+    /// Writes user register 2 (0x12 and 0x13)
+    pub fn set_user_register2(&mut self, value: u16) -> Result<(), Error<E>> {
+        self.write_registers(Register::UserReg2.address(), &value.to_be_bytes())
+    }
+
+    /// Runs `f` until it succeeds, fails with a permanent error or the retry
+    /// policy runs out of attempts
+    fn retrying<T>(
+        &mut self,
+        mut f: impl FnMut(&mut Self) -> Result<T, Error<E>>,
+    ) -> Result<T, Error<E>> {
+        self.last_attempts = 0;
+        loop {
+            if self.last_attempts > 0 {
+                self.delay.delay_us(self.retry.backoff_us);
+            }
+            self.last_attempts += 1;
+            match f(self) {
+                Err(e) if e.is_transient() && self.last_attempts < self.retry.attempts => {}
+                result => return result,
+            }
+        }
+    }
+
+    /// Wakes the sensor up, sends `command` and reads back its `response`
+    ///
+    /// This abandons any split-phase measurement, whose command won't be
+    /// answered anymore.
+    fn transfer(&mut self, command: &[u8], response: &mut [u8]) -> Result<(), Error<E>> {
+        self.pending = None;
+        self.wake_up();
         // Wait at least 0.8ms, at most 3ms.
-        self.delay.delay_us(900);
+        self.delay.delay_us(self.timing.wake_us());
 
-        // Send read command.
+        self.exchange(command, response)
+    }
+
+    /// Sends `command` to the awake sensor and reads back its `response`
+    fn exchange(&mut self, command: &[u8], response: &mut [u8]) -> Result<(), Error<E>> {
+        // Send command.
         self.device
-            .write(DEVICE_I2C_ADDR, &[0x03, 0x00, 0x04])
-            .map_err(|_| Error::WriteError)?;
+            .write(self.address, command)
+            .map_err(Error::WriteError)?;
         // Wait at least 1.5ms for the result.
-        self.delay.delay_us(1600);
-
-        // read out 8 bytes of result data
-        // byte 0: Should be Modbus function code 0x03
-        // byte 1: Should be number of registers to read (0x04)
-        // byte 2: Humidity msb
-        // byte 3: Humidity lsb
-        // byte 4: Temperature msb
-        // byte 5: Temperature lsb
-        // byte 6: CRC lsb byte
-        // byte 7: CRC msb byte
-        let mut data = [0; 8];
+        self.delay.delay_us(self.timing.conversion_us());
+
         self.device
-            .read(DEVICE_I2C_ADDR, &mut data)
-            .map_err(|_| Error::ReadError)?;
+            .read(self.address, response)
+            .map_err(Error::ReadError)
+    }
+}
 
-        // check that the operation was reported as succesful
-        if data[0] != 0x03 || data[1] != 0x04 {
-            return Err(Error::SensorError);
-        }
+/// Builds the error returned when polling `wait_us` too soon
+fn too_soon<E>(wait_us: u64) -> Error<E> {
+    Error::TooSoon {
+        wait_ms: wait_us.div_ceil(1000) as u32,
+    }
+}
 
-        // CRC check
-        let crc = crc16(&data[0..6]);
-        if crc != u16::from_le_bytes([data[6], data[7]]) {
-            return Err(Error::SensorError);
-        }
+/// Checks the acknowledgement of a write of `len` registers at `start`
+fn check_write_response<E>(data: &[u8; 5], start: u8, len: usize) -> Result<(), Error<E>> {
+    match protocol::decode_response(data)? {
+        Response::Write { start: s, len: l } if s == start && usize::from(l) == len => Ok(()),
+        Response::Exception {
+            function: WRITE_REGISTERS,
+            exception,
+        } => Err(Error::ModbusException(exception)),
+        _ => Err(Error::UnexpectedHeader),
+    }
+}
 
-        let mut temperature = i16::from_be_bytes([data[4] & 0b0111_1111, data[5]]);
-        if data[4] & 0b1000_0000 != 0 {
-            temperature = -temperature;
-        }
+/// Checks the response to a read command and returns the register values
+///
+/// `data` is exactly as long as the expected response.
+fn check_read_response<E>(data: &[u8]) -> Result<&[u8], Error<E>> {
+    match protocol::decode_response(data)? {
+        Response::Read(registers) if registers.len() + 4 == data.len() => Ok(registers),
+        Response::Exception {
+            function: READ_REGISTERS,
+            exception,
+        } => Err(Error::ModbusException(exception)),
+        _ => Err(Error::UnexpectedHeader),
+    }
+}
 
-        let humidity = u16::from_be_bytes([data[2], data[3]]);
+#[cfg(feature = "float")]
+#[test]
+fn test_read() {
+    let mut am2320 = Am2320::new(
+        mock::I2c::new(&[&[0x03, 0x04, 0x02, 0x36, 0x80, 0x65, 0xb1, 0xb5]]),
+        mock::Delay,
+    );
+    let measurement = am2320.read().unwrap();
+    assert_eq!(measurement.humidity.percent(), 56.6);
+    assert_eq!(measurement.temperature.celsius(), -10.1);
+    assert_eq!(am2320.device.writes, [&[0x00][..], &[0x03, 0x00, 0x04]]);
+}
 
-        Ok(Measurement {
-            temperature: f32::from(temperature) / 10.0,
-            humidity: f32::from(humidity) / 10.0,
+#[test]
+fn test_read_registers() {
+    let mut am2320 = Am2320::new(
+        mock::I2c::new(&[
+            &[0x03, 0x02, 0x00, 0x00, 0xa1, 0xa0],
+            &[0x03, 0x02, 0x00, 0x00, 0x00, 0x00],
+            &[0x03, 0x04, 0x00, 0x00, 0xa1, 0xa0],
+            &[0x83, 0x81, 0xa0, 0xe0, 0x00, 0x00],
+        ]),
+        mock::Delay,
+    );
+    let mut registers = [0xff; 2];
+    am2320.read_registers(0x10, &mut registers).unwrap();
+    assert_eq!(registers, [0x00, 0x00]);
+    assert_eq!(am2320.device.writes[1], [0x03, 0x10, 0x02]);
+    assert!(matches!(
+        am2320.read_registers(0x10, &mut registers),
+        Err(Error::CrcMismatch {
+            expected: 0xa0a1,
+            actual: 0x0000
         })
+    ));
+    assert!(matches!(
+        am2320.read_registers(0x10, &mut registers),
+        Err(Error::UnexpectedHeader)
+    ));
+    assert!(matches!(
+        am2320.read_registers(0x10, &mut registers),
+        Err(Error::ModbusException(ModbusException::IllegalAddress))
+    ));
+
+    assert!(matches!(
+        am2320.read_registers(0x00, &mut [0; MAX_REGISTERS + 1]),
+        Err(Error::InvalidRegisterRange)
+    ));
+    assert!(matches!(
+        am2320.read_registers(0x1f, &mut [0; 2]),
+        Err(Error::InvalidRegisterRange)
+    ));
+    assert!(matches!(
+        am2320.read_registers(0x00, &mut []),
+        Err(Error::InvalidRegisterRange)
+    ));
+}
+
+#[test]
+fn test_write_registers() {
+    let mut am2320 = Am2320::new(
+        mock::I2c::new(&[
+            &[0x10, 0x10, 0x02, 0xfc, 0x04],
+            &[0x90, 0x84, 0x6d, 0xd3, 0x00],
+        ]),
+        mock::Delay,
+    );
+    am2320.set_user_register1(0x1234).unwrap();
+    assert_eq!(
+        am2320.device.writes[1],
+        [0x10, 0x10, 0x02, 0x12, 0x34, 0x4d, 0xb4]
+    );
+    assert!(matches!(
+        am2320.write_registers(0x00, &[0x00]),
+        Err(Error::ModbusException(ModbusException::WriteDisabled))
+    ));
+}
+
+#[test]
+fn test_identify() {
+    let mut am2320 = Am2320::new(
+        mock::I2c::new(&[&[
+            0x03, 0x07, 0x32, 0x20, 0x01, 0x12, 0x34, 0x56, 0x78, 0x0d, 0xb1,
+        ]]),
+        mock::Delay,
+    );
+    let info = am2320.identify().unwrap();
+    assert_eq!(am2320.device.writes[1], [0x03, 0x08, 0x07]);
+    assert_eq!(info.model, 0x3220);
+    assert_eq!(info.version, 0x01);
+    assert_eq!(info.id, 0x1234_5678);
+    assert!(!info.is_blank());
+}
+
+#[test]
+fn test_bus_error() {
+    use embedded_hal::i2c::Error as _;
+
+    let mut am2320 = Am2320::new(mock::I2c::new(&[]), mock::Delay);
+    let error = am2320.read_raw().unwrap_err();
+    assert!(matches!(error, Error::ReadError(i2c::ErrorKind::Bus)));
+    assert_eq!(error.kind(), i2c::ErrorKind::Bus);
+}
+
+#[test]
+fn test_sampling_interval() {
+    use core::cell::Cell;
+
+    let response: &[u8] = &[0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x50, 0x05];
+    let now = Cell::new(0);
+    let mut am2320 = Am2320::new(mock::I2c::new(&[response, response]), mock::Delay)
+        .with_clock(mock::Clock(&now), SamplingPolicy::Reject);
+    am2320.read_raw().unwrap();
+
+    now.set(500_000);
+    assert!(matches!(
+        am2320.read_raw(),
+        Err(Error::TooSoon { wait_ms: 1500 })
+    ));
+
+    now.set(MIN_SAMPLING_INTERVAL_US);
+    am2320.read_raw().unwrap();
+    assert_eq!(am2320.device.writes.len(), 4);
+
+    let mut am2320 = am2320.with_clock(mock::Clock(&now), SamplingPolicy::Cached);
+    am2320.device = mock::I2c::new(&[response]);
+    am2320.read_raw().unwrap();
+    assert_eq!(am2320.read_raw().unwrap().humidity_permille, 566);
+    assert_eq!(am2320.device.writes.len(), 2);
+}
+
+#[test]
+fn test_retry() {
+    let mut am2320 = Am2320::new(
+        mock::I2c::new(&[
+            &[0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x00, 0x00],
+            &[0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x50, 0x05],
+            &[0x90, 0x84, 0x6d, 0xd3, 0x00],
+        ]),
+        mock::Delay,
+    )
+    .with_retry(RetryPolicy::new(3, 1000));
+    am2320.read_raw().unwrap();
+    assert_eq!(am2320.last_attempts(), 2);
+
+    assert!(matches!(
+        am2320.set_user_register1(0),
+        Err(Error::ModbusException(ModbusException::WriteDisabled))
+    ));
+    assert_eq!(am2320.last_attempts(), 1);
+
+    assert!(matches!(am2320.read_raw(), Err(Error::ReadError(_))));
+    assert_eq!(am2320.last_attempts(), 3);
+}
+
+#[test]
+fn test_release() {
+    let response: &[u8] = &[0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x50, 0x05];
+    let mut bus = mock::I2c::new(&[response, response]);
+
+    // The driver only borrows the bus
+    Am2320::new(&mut bus, mock::Delay).read_raw().unwrap();
+    assert_eq!(bus.writes.len(), 2);
+    bus.writes.clear();
+
+    let mut am2320 = Am2320::new(bus, mock::Delay);
+    am2320.read_raw().unwrap();
+    assert_eq!(am2320.device_mut().writes.len(), 2);
+    let (bus, _) = am2320.release();
+    assert_eq!(bus.writes[1], [0x03, 0x00, 0x04]);
+}
+
+#[test]
+fn test_health_check() {
+    let mut am2320 = Am2320::new(
+        mock::I2c::new(&[
+            &[
+                0x03, 0x08, 0x32, 0x20, 0x01, 0x12, 0x34, 0x56, 0x78, 0x00, 0x31, 0x35,
+            ],
+            &[0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x50, 0x05],
+        ]),
+        mock::Delay,
+    );
+    let report = am2320.health_check().unwrap();
+    assert_eq!(report.info.id, 0x1234_5678);
+    assert!(report.status.is_nominal());
+    assert_eq!(report.measurement.humidity_permille, 566);
+    assert!(report.is_healthy());
+    // Only the first command wakes the sensor up
+    assert_eq!(am2320.device.writes.len(), 3);
+    assert_eq!(am2320.device.writes[2], [0x03, 0x00, 0x04]);
+    assert_eq!(am2320.last_attempts(), 2);
+
+    // A retried measurement wakes the sensor up again
+    am2320.device = mock::I2c::new(&[
+        &[
+            0x03, 0x08, 0x32, 0x20, 0x01, 0x12, 0x34, 0x56, 0x78, 0x00, 0x31, 0x35,
+        ],
+        &[0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x00, 0x00],
+        &[0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x50, 0x05],
+    ]);
+    am2320.retry = RetryPolicy::new(2, 0);
+    am2320.health_check().unwrap();
+    assert_eq!(
+        am2320.device.writes[3..],
+        [&[0x00][..], &[0x03, 0x00, 0x04]]
+    );
+    assert_eq!(am2320.last_attempts(), 3);
+}
+
+#[test]
+fn test_dump_all() {
+    let mut responses = [[0; 14], [0; 14], [0; 14], [0; 14]];
+    let lens = [10, 10, 10, 2];
+    for (response, len) in responses.iter_mut().zip(lens) {
+        response[0] = 0x03;
+        response[1] = len;
+        let n = usize::from(len) + 2;
+        let crc = protocol::crc16(&response[..n]);
+        response[n..n + 2].copy_from_slice(&crc.to_le_bytes());
+    }
+    // Status register
+    responses[1][2 + 5] = 0x42;
+    let crc = protocol::crc16(&responses[1][..12]);
+    responses[1][12..14].copy_from_slice(&crc.to_le_bytes());
+
+    let mut am2320 = Am2320::new(
+        mock::I2c::new(&[
+            &responses[0][..],
+            &responses[1][..],
+            &responses[2][..],
+            &responses[3][..6],
+        ]),
+        mock::Delay,
+    );
+    let registers = am2320.dump_all().unwrap();
+    assert_eq!(registers[Register::Status.address() as usize], 0x42);
+    let commands = [
+        [0x03, 0x00, 0x0a],
+        [0x03, 0x0a, 0x0a],
+        [0x03, 0x14, 0x0a],
+        [0x03, 0x1e, 0x02],
+    ];
+    for (i, command) in commands.iter().enumerate() {
+        assert_eq!(am2320.device.writes[2 * i + 1], command);
     }
 }
 
+#[cfg(feature = "serde")]
 #[test]
-fn test_crc16() {
-    assert_eq!(crc16(&[]), 0xFFFF);
-    assert_eq!(crc16(&[0x03, 0x04, 0x02, 0x36, 0x0, 0xDB]), 0x0550);
+fn test_serde() {
+    let info = DeviceInfo {
+        model: 0x3220,
+        version: 0x01,
+        id: 0x1234_5678,
+    };
+    let json = serde_json::to_string(&info).unwrap();
+    assert_eq!(json, r#"{"model":12832,"version":1,"id":305419896}"#);
+    assert_eq!(serde_json::from_str::<DeviceInfo>(&json).unwrap(), info);
 }
@@ -0,0 +1,21 @@
+//! Constructors for Linux hosts, over `linux-embedded-hal`'s `I2cdev`
+
+use std::path::Path;
+
+use linux_embedded_hal::{i2cdev::linux::LinuxI2CError, Delay, I2cdev};
+
+use crate::{Am2320, DEVICE_I2C_ADDR};
+
+impl Am2320<I2cdev, Delay> {
+    /// Create a AM2320 temperature sensor driver on the I2C bus at `path`,
+    /// such as `/dev/i2c-1` on a Raspberry Pi.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, LinuxI2CError> {
+        Self::open_with_address(path, DEVICE_I2C_ADDR)
+    }
+
+    /// Create a AM2320 temperature sensor driver talking to `address` on the
+    /// I2C bus at `path`.
+    pub fn open_with_address<P: AsRef<Path>>(path: P, address: u8) -> Result<Self, LinuxI2CError> {
+        Ok(Self::new_with_address(I2cdev::new(path)?, Delay, address))
+    }
+}
@@ -0,0 +1,192 @@
+//! Measurements decoded from the sensor registers
+
+use core::fmt;
+
+use crate::Error;
+#[cfg(feature = "float")]
+use crate::{RelativeHumidity, Temperature};
+
+/// Measurement from the sensor in fixed-point units, as sent by the sensor
+///
+/// This type doesn't involve any floating-point arithmetic, which keeps
+/// soft-float code out of targets without an FPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RawMeasurement {
+    /// Humidity in tenths of a percent (‰)
+    pub humidity_permille: u16,
+    /// Temperature in tenths of a degree celsius (0.1 °C)
+    pub temperature_decicelsius: i16,
+}
+
+impl RawMeasurement {
+    /// Lowest temperature the sensor can measure according to the datasheet
+    pub const MIN_TEMPERATURE_DECICELSIUS: i16 = -400;
+    /// Highest temperature the sensor can measure according to the datasheet
+    pub const MAX_TEMPERATURE_DECICELSIUS: i16 = 800;
+    /// Highest humidity the sensor can measure according to the datasheet
+    pub const MAX_HUMIDITY_PERMILLE: u16 = 999;
+
+    /// Returns `true` if both values are within the datasheet limits
+    pub fn is_in_range(&self) -> bool {
+        (Self::MIN_TEMPERATURE_DECICELSIUS..=Self::MAX_TEMPERATURE_DECICELSIUS)
+            .contains(&self.temperature_decicelsius)
+            && self.humidity_permille <= Self::MAX_HUMIDITY_PERMILLE
+    }
+
+    /// Returns the measurement with both values clamped to the datasheet limits
+    pub fn clamped(&self) -> Self {
+        Self {
+            humidity_permille: self.humidity_permille.min(Self::MAX_HUMIDITY_PERMILLE),
+            temperature_decicelsius: self.temperature_decicelsius.clamp(
+                Self::MIN_TEMPERATURE_DECICELSIUS,
+                Self::MAX_TEMPERATURE_DECICELSIUS,
+            ),
+        }
+    }
+
+    /// Humidity in thousandths of a percent (m%)
+    pub fn humidity_millipercent(&self) -> u32 {
+        u32::from(self.humidity_permille) * 100
+    }
+
+    /// Temperature in thousandths of a degree celsius (m°C)
+    pub fn temperature_millicelsius(&self) -> i32 {
+        i32::from(self.temperature_decicelsius) * 100
+    }
+}
+
+impl fmt::Display for RawMeasurement {
+    /// Formats the values with one decimal, without floating-point arithmetic
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let temperature = self.temperature_decicelsius;
+        let sign = if temperature < 0 { "-" } else { "" };
+        let temperature = temperature.unsigned_abs();
+        write!(
+            f,
+            "{}.{} %, {}{}.{} °C",
+            self.humidity_permille / 10,
+            self.humidity_permille % 10,
+            sign,
+            temperature / 10,
+            temperature % 10
+        )
+    }
+}
+
+/// Representation of a measurement from the sensor
+#[cfg(feature = "float")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Measurement {
+    /// Temperature
+    pub temperature: Temperature,
+    /// Relative humidity
+    pub humidity: RelativeHumidity,
+}
+
+#[cfg(feature = "float")]
+impl From<RawMeasurement> for Measurement {
+    fn from(raw: RawMeasurement) -> Self {
+        Measurement {
+            temperature: Temperature::from_celsius(f32::from(raw.temperature_decicelsius) / 10.0),
+            humidity: RelativeHumidity::from_percent(f32::from(raw.humidity_permille) / 10.0),
+        }
+    }
+}
+
+/// What to do with measurements outside of the datasheet limits
+///
+/// The sensor operates between -40 °C and 80 °C, and between 0 % and 99.9 %
+/// relative humidity. Values outside of these limits usually come from a
+/// glitching sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RangePolicy {
+    /// Fail with `Error::OutOfRange`, this is the default
+    #[default]
+    Reject,
+    /// Clamp the values to the datasheet limits
+    Clamp,
+    /// Return the values as they were sent by the sensor
+    PassThrough,
+}
+
+impl RangePolicy {
+    /// Applies the policy to `measurement`
+    pub(crate) fn apply<E>(self, measurement: RawMeasurement) -> Result<RawMeasurement, Error<E>> {
+        self.check(measurement).map_err(Error::OutOfRange)
+    }
+
+    /// Applies the policy to `measurement`, returning it back if it is rejected
+    pub(crate) fn check(
+        self,
+        measurement: RawMeasurement,
+    ) -> Result<RawMeasurement, RawMeasurement> {
+        match self {
+            _ if measurement.is_in_range() => Ok(measurement),
+            RangePolicy::Reject => Err(measurement),
+            RangePolicy::Clamp => Ok(measurement.clamped()),
+            RangePolicy::PassThrough => Ok(measurement),
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::protocol::decode_measurement;
+
+#[test]
+fn test_decode_measurement() {
+    extern crate std;
+
+    let raw = decode_measurement(&[0x02, 0x36, 0x80, 0x65]);
+    assert_eq!(raw.humidity_permille, 566);
+    assert_eq!(raw.temperature_decicelsius, -101);
+    assert_eq!(raw.humidity_millipercent(), 56_600);
+    assert_eq!(raw.temperature_millicelsius(), -10_100);
+    assert_eq!(std::format!("{}", raw), "56.6 %, -10.1 °C");
+    let raw = decode_measurement(&[0x00, 0x05, 0x80, 0x05]);
+    assert_eq!(std::format!("{}", raw), "0.5 %, -0.5 °C");
+}
+
+#[test]
+fn test_range_policy() {
+    let glitch = decode_measurement(&[0xff, 0xff, 0xff, 0xff]);
+    assert!(!glitch.is_in_range());
+    assert_eq!(
+        RangePolicy::Reject.apply::<()>(glitch),
+        Err(Error::OutOfRange(glitch))
+    );
+    assert_eq!(
+        RangePolicy::Clamp.apply::<()>(glitch),
+        Ok(RawMeasurement {
+            humidity_permille: 999,
+            temperature_decicelsius: -400,
+        })
+    );
+    assert_eq!(RangePolicy::PassThrough.apply::<()>(glitch), Ok(glitch));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde() {
+    let raw = RawMeasurement {
+        humidity_permille: 566,
+        temperature_decicelsius: -101,
+    };
+    let json = serde_json::to_string(&raw).unwrap();
+    assert_eq!(
+        json,
+        r#"{"humidity_permille":566,"temperature_decicelsius":-101}"#
+    );
+    assert_eq!(serde_json::from_str::<RawMeasurement>(&json).unwrap(), raw);
+
+    #[cfg(feature = "float")]
+    assert_eq!(
+        serde_json::to_string(&Measurement::from(raw)).unwrap(),
+        r#"{"temperature":-10.1,"humidity":56.6}"#
+    );
+}
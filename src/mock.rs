@@ -0,0 +1,185 @@
+//! Scripted I2C bus, pin and delays used by the unit tests
+extern crate std;
+
+use core::{cell::Cell, convert::Infallible};
+use std::vec::Vec;
+
+use embedded_hal::{delay, digital, i2c};
+
+/// I2C bus replaying canned responses, one per `read`
+///
+/// Like the real sensor, the wake-up write (`[0x00]`) is never acknowledged.
+pub struct I2c {
+    responses: Vec<Vec<u8>>,
+    /// Every write issued to the bus, in order
+    pub writes: Vec<Vec<u8>>,
+    /// Address of every write issued to the bus, in order
+    pub addresses: Vec<u8>,
+}
+
+impl I2c {
+    pub fn new(responses: &[&[u8]]) -> Self {
+        Self {
+            responses: responses.iter().rev().map(|r| r.to_vec()).collect(),
+            writes: Vec::new(),
+            addresses: Vec::new(),
+        }
+    }
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), i2c::ErrorKind> {
+        self.addresses.push(address);
+        self.writes.push(bytes.to_vec());
+        if bytes == [0x00] {
+            return Err(i2c::ErrorKind::NoAcknowledge(
+                i2c::NoAcknowledgeSource::Address,
+            ));
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<(), i2c::ErrorKind> {
+        let response = self.responses.pop().ok_or(i2c::ErrorKind::Bus)?;
+        buffer.copy_from_slice(&response[..buffer.len()]);
+        Ok(())
+    }
+}
+
+impl i2c::ErrorType for I2c {
+    type Error = i2c::ErrorKind;
+}
+
+impl i2c::I2c for I2c {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        for operation in operations {
+            match operation {
+                i2c::Operation::Write(bytes) => self.write(address, bytes)?,
+                i2c::Operation::Read(buffer) => self.read(buffer)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::i2c::I2c for I2c {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        i2c::I2c::transaction(self, address, operations)
+    }
+}
+
+/// Delay returning immediately
+pub struct Delay;
+
+impl delay::DelayNs for Delay {
+    fn delay_ns(&mut self, _ns: u32) {}
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for Delay {
+    async fn delay_ns(&mut self, _ns: u32) {}
+}
+
+/// Clock reading the time from a shared cell, delays advance it
+pub struct Clock<'a>(pub &'a Cell<u64>);
+
+impl crate::Clock for Clock<'_> {
+    fn now_us(&mut self) -> u64 {
+        self.0.get()
+    }
+}
+
+impl delay::DelayNs for Clock<'_> {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.set(self.0.get() + u64::from(ns).div_ceil(1000));
+    }
+}
+
+/// Open-drain pin replaying a waveform once the host releases it
+///
+/// The waveform is a list of levels (`true` for high) and how many
+/// microseconds they last, the line stays high past its end.
+pub struct Pin<'a> {
+    time: &'a Cell<u64>,
+    waveform: Vec<(bool, u64)>,
+    driven_low: bool,
+    released_at: Option<u64>,
+}
+
+impl<'a> Pin<'a> {
+    pub fn new(time: &'a Cell<u64>, waveform: Vec<(bool, u64)>) -> Self {
+        Self {
+            time,
+            waveform,
+            driven_low: false,
+            released_at: None,
+        }
+    }
+
+    fn level(&self) -> bool {
+        if self.driven_low {
+            return false;
+        }
+        let mut elapsed = match self.released_at {
+            Some(released_at) => self.time.get() - released_at,
+            None => return true,
+        };
+        for &(level, duration) in &self.waveform {
+            if elapsed < duration {
+                return level;
+            }
+            elapsed -= duration;
+        }
+        true
+    }
+}
+
+impl digital::ErrorType for Pin<'_> {
+    type Error = Infallible;
+}
+
+impl digital::OutputPin for Pin<'_> {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.driven_low = true;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        if self.driven_low {
+            self.driven_low = false;
+            self.released_at = Some(self.time.get());
+        }
+        Ok(())
+    }
+}
+
+impl digital::InputPin for Pin<'_> {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.level())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        Ok(!self.level())
+    }
+}
+
+/// Polls a future to completion, the mocks never return `Pending`
+#[cfg(feature = "async")]
+pub fn block_on<F: core::future::Future>(future: F) -> F::Output {
+    use core::task::{Context, Poll, Waker};
+
+    let mut future = core::pin::pin!(future);
+    let mut context = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Support for sensors sitting behind a TCA9548A I2C multiplexer
+
+use core::cell::{Cell, RefCell};
+
+use embedded_hal::i2c;
+
+/// TCA9548A I2C multiplexer owning the upstream bus
+///
+/// Since every AM2320 answers on the same address, several of them can only
+/// share a bus if each one is wired to its own multiplexer channel. Each
+/// [`Tca9548aChannel`] implements `i2c::I2c` and selects its channel before
+/// forwarding transactions, so one driver can be created per channel:
+///
+/// ```ignore
+/// let mux = Tca9548a::new(i2c, 0x70);
+/// let mut first = Am2320::new(mux.channel(0), delay.clone());
+/// let mut second = Am2320::new(mux.channel(1), delay);
+/// ```
+pub struct Tca9548a<I2C> {
+    /// Upstream I2C bus
+    bus: RefCell<I2C>,
+    /// I2C address of the multiplexer
+    address: u8,
+    /// Last channel selected, to avoid re-selecting it on every transaction
+    selected: Cell<Option<u8>>,
+}
+
+impl<I2C: i2c::I2c> Tca9548a<I2C> {
+    /// Create a TCA9548A driver, `address` is between 0x70 and 0x77
+    /// depending on the A0-A2 pins.
+    pub fn new(bus: I2C, address: u8) -> Self {
+        Self {
+            bus: RefCell::new(bus),
+            address,
+            selected: Cell::new(None),
+        }
+    }
+
+    /// Returns a bus only reaching the devices wired to `channel`
+    ///
+    /// Panics if `channel` is greater than 7.
+    pub fn channel(&self, channel: u8) -> Tca9548aChannel<'_, I2C> {
+        assert!(channel < 8, "the TCA9548A only has 8 channels");
+        Tca9548aChannel { mux: self, channel }
+    }
+
+    /// Releases the upstream I2C bus
+    pub fn release(self) -> I2C {
+        self.bus.into_inner()
+    }
+
+    fn select(&self, bus: &mut I2C, channel: u8) -> Result<(), I2C::Error> {
+        if self.selected.get() != Some(channel) {
+            self.selected.set(None);
+            bus.write(self.address, &[1 << channel])?;
+            self.selected.set(Some(channel));
+        }
+        Ok(())
+    }
+}
+
+/// One channel of a [`Tca9548a`] multiplexer
+pub struct Tca9548aChannel<'a, I2C> {
+    mux: &'a Tca9548a<I2C>,
+    channel: u8,
+}
+
+impl<I2C: i2c::I2c> i2c::ErrorType for Tca9548aChannel<'_, I2C> {
+    type Error = I2C::Error;
+}
+
+impl<I2C: i2c::I2c> i2c::I2c for Tca9548aChannel<'_, I2C> {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut bus = self.mux.bus.borrow_mut();
+        self.mux.select(&mut bus, self.channel)?;
+        bus.transaction(address, operations)
+    }
+}
+
+#[test]
+fn test_channels() {
+    use crate::{mock, Am2320, DEVICE_I2C_ADDR};
+
+    let response: &[u8] = &[0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x50, 0x05];
+    let mux = Tca9548a::new(mock::I2c::new(&[response, response, response]), 0x70);
+    let mut first = Am2320::new(mux.channel(0), mock::Delay);
+    let mut second = Am2320::new(mux.channel(3), mock::Delay);
+    first.read_raw().unwrap();
+    second.read_raw().unwrap();
+    first.read_raw().unwrap();
+
+    let bus = mux.release();
+    let selects = bus
+        .addresses
+        .iter()
+        .zip(&bus.writes)
+        .filter(|(address, _)| **address == 0x70)
+        .map(|(_, write)| write[0]);
+    assert!(selects.eq([0b0000_0001, 0b0000_1000, 0b0000_0001]));
+    assert!(bus
+        .addresses
+        .iter()
+        .all(|address| *address == 0x70 || *address == DEVICE_I2C_ADDR));
+}
@@ -0,0 +1,321 @@
+//! Split-phase measurements for main loops that can't block
+//!
+//! A measurement spends ~2.5 ms waiting for the sensor to wake up and then
+//! to convert. `start_measurement` and `finish_measurement` let the caller do
+//! something else in the meantime, tracking the delays with the driver's
+//! `Clock` instead of blocking on a `DelayNs`.
+//!
+//! The sensor goes back to sleep `Timing::MAX_WAKE_US` (3 ms) after the last
+//! transaction, so `finish_measurement` has to be called between `wake_us`
+//! and 3 ms after the wake-up, then between `conversion_us` and 3 ms after
+//! the command. A late call wakes the sensor up again and starts over, which
+//! means a main loop polling less often than every ~2 ms never completes a
+//! measurement.
+
+use embedded_hal::i2c;
+
+use crate::{
+    check_read_response,
+    protocol::{decode_measurement, encode_read_request},
+    too_soon, Am2320, Clock, Error, RawMeasurement, SamplingPolicy, Timing, DEVICE_I2C_ADDR,
+};
+
+/// Step of a split-phase measurement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Pending {
+    /// The sensor was woken up at the given time
+    Waking(u64),
+    /// The command was sent at the given time
+    Converting(u64),
+}
+
+/// Delay for drivers that never block
+///
+/// It doesn't implement `DelayNs`, so a driver built with
+/// [`Am2320::new_nonblocking`] only offers the split-phase API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NoDelay;
+
+impl<I2C, C, E> Am2320<I2C, NoDelay, C>
+where
+    I2C: i2c::I2c<Error = E>,
+    C: Clock,
+{
+    /// Create a AM2320 temperature sensor driver that never blocks.
+    ///
+    /// `clock` times the split-phase measurements, see `start_measurement`.
+    pub fn new_nonblocking(device: I2C, clock: C) -> Self {
+        Self::new_nonblocking_with_address(device, clock, DEVICE_I2C_ADDR)
+    }
+
+    /// Create a AM2320 temperature sensor driver that never blocks, talking
+    /// to `address`.
+    ///
+    /// See `new_with_address` for when the address differs from
+    /// `DEVICE_I2C_ADDR`.
+    pub fn new_nonblocking_with_address(device: I2C, clock: C, address: u8) -> Self {
+        Self::from_parts(device, NoDelay, clock, address)
+    }
+}
+
+impl<I2C, Delay, C, E> Am2320<I2C, Delay, C>
+where
+    I2C: i2c::I2c<Error = E>,
+    C: Clock,
+{
+    /// Wakes the sensor up to start a measurement
+    ///
+    /// Then call `finish_measurement` until it stops returning `WouldBlock`.
+    /// Starting a new measurement abandons the pending one, and so does any
+    /// blocking transfer such as `read_raw` or `read_registers`.
+    ///
+    /// When a `SamplingPolicy` was set with `with_sampling_policy` or
+    /// `with_clock` and the last measurement is too recent, nothing is
+    /// started: `SamplingPolicy::Reject` fails with `Error::TooSoon`, while
+    /// with the other policies `finish_measurement` returns the cached
+    /// measurement or waits.
+    pub fn start_measurement(&mut self) -> Result<(), Error<E>> {
+        if let Some((policy, wait_us, _)) = self.sampling_wait() {
+            self.pending = None;
+            return match policy {
+                SamplingPolicy::Reject => Err(too_soon(wait_us)),
+                SamplingPolicy::Cached | SamplingPolicy::Wait => Ok(()),
+            };
+        }
+        let now = self.clock.now_us();
+        self.wake(now);
+        Ok(())
+    }
+
+    /// Advances the measurement started with `start_measurement`
+    ///
+    /// Sends the command once the sensor is awake, then reads the result once
+    /// the conversion is over, returning `WouldBlock` until then. When no
+    /// measurement is pending, starts one. Transfers aren't retried, the
+    /// `RetryPolicy` only applies to the blocking API.
+    ///
+    /// When a `SamplingPolicy` was set, starting a measurement less than
+    /// `MIN_SAMPLING_INTERVAL_US` after the last one applies it: `Cached`
+    /// returns the last measurement, `Reject` fails with `Error::TooSoon` and
+    /// `Wait` returns `WouldBlock` until the interval elapsed.
+    ///
+    /// When called more than `Timing::MAX_WAKE_US` after the wake-up or the
+    /// command, the sensor is asleep again: the measurement restarts with a
+    /// new wake-up and `WouldBlock` is returned.
+    ///
+    /// ```
+    /// # fn measure<I2C: embedded_hal::i2c::I2c, C: am2320::Clock>(
+    /// #     am2320: &mut am2320::Am2320<I2C, am2320::NoDelay, C>,
+    /// # ) -> Result<(), am2320::Error<I2C::Error>> {
+    /// let measurement = nb::block!(am2320.finish_measurement())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn finish_measurement(&mut self) -> nb::Result<RawMeasurement, Error<E>> {
+        let now = self.clock.now_us();
+        match self.pending {
+            None => {
+                if let Some((policy, wait_us, measurement)) = self.sampling_wait() {
+                    return match policy {
+                        SamplingPolicy::Cached => Ok(measurement),
+                        SamplingPolicy::Reject => Err(nb::Error::Other(too_soon(wait_us))),
+                        SamplingPolicy::Wait => Err(nb::Error::WouldBlock),
+                    };
+                }
+                self.wake(now);
+                Err(nb::Error::WouldBlock)
+            }
+            Some(Pending::Waking(since)) => {
+                let elapsed = now.saturating_sub(since);
+                if elapsed < u64::from(self.timing.wake_us()) {
+                    return Err(nb::Error::WouldBlock);
+                }
+                if elapsed > u64::from(Timing::MAX_WAKE_US) {
+                    self.wake(now);
+                    return Err(nb::Error::WouldBlock);
+                }
+                self.pending = None;
+                let command = encode_read_request(0x00, 4).map_err(Error::from)?;
+                self.device
+                    .write(self.address, &command)
+                    .map_err(Error::WriteError)?;
+                self.pending = Some(Pending::Converting(now));
+                Err(nb::Error::WouldBlock)
+            }
+            Some(Pending::Converting(since)) => {
+                let elapsed = now.saturating_sub(since);
+                let conversion_us = self.timing.conversion_us();
+                if elapsed < u64::from(conversion_us) {
+                    return Err(nb::Error::WouldBlock);
+                }
+                if elapsed > u64::from(conversion_us.max(Timing::MAX_WAKE_US)) {
+                    self.wake(now);
+                    return Err(nb::Error::WouldBlock);
+                }
+                self.pending = None;
+                let mut response = [0; 8];
+                self.device
+                    .read(self.address, &mut response)
+                    .map_err(Error::ReadError)?;
+
+                let mut data = [0; 4];
+                data.copy_from_slice(check_read_response(&response)?);
+                let measurement = self.range.apply(decode_measurement(&data))?;
+                if self.sampling.is_some() {
+                    self.last_sample = Some((now, measurement));
+                }
+                Ok(measurement)
+            }
+        }
+    }
+
+    /// Returns `true` while a split-phase measurement is pending
+    pub fn is_measuring(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Wakes the sensor up at `now`, abandoning any pending measurement
+    fn wake(&mut self, now: u64) {
+        self.wake_up();
+        self.pending = Some(Pending::Waking(now));
+    }
+}
+
+#[test]
+fn test_split_phase() {
+    use crate::mock;
+    use core::cell::Cell;
+
+    let response: &[u8] = &[0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x50, 0x05];
+    let now = Cell::new(0);
+    let mut am2320 = Am2320::new_nonblocking(mock::I2c::new(&[response]), mock::Clock(&now));
+    am2320.start_measurement().unwrap();
+    assert!(am2320.is_measuring());
+    assert_eq!(am2320.finish_measurement(), Err(nb::Error::WouldBlock));
+    assert_eq!(am2320.device.writes.len(), 1);
+
+    now.set(900);
+    assert_eq!(am2320.finish_measurement(), Err(nb::Error::WouldBlock));
+    assert_eq!(am2320.device.writes[1], [0x03, 0x00, 0x04]);
+
+    now.set(2000);
+    assert_eq!(am2320.finish_measurement(), Err(nb::Error::WouldBlock));
+    now.set(2500);
+    let measurement = am2320.finish_measurement().unwrap();
+    assert_eq!(measurement.humidity_permille, 566);
+    assert!(!am2320.is_measuring());
+
+    // Without a pending measurement, finishing starts one
+    assert_eq!(am2320.finish_measurement(), Err(nb::Error::WouldBlock));
+    assert!(am2320.is_measuring());
+}
+
+#[test]
+fn test_address() {
+    use crate::mock;
+    use core::cell::Cell;
+
+    let now = Cell::new(0);
+    let mut am2320 =
+        Am2320::new_nonblocking_with_address(mock::I2c::new(&[]), mock::Clock(&now), 0x5d);
+    am2320.start_measurement().unwrap();
+    assert_eq!(am2320.device.addresses, [0x5d]);
+}
+
+#[test]
+fn test_sampling_policy() {
+    use crate::mock;
+    use core::cell::Cell;
+
+    let response: &[u8] = &[0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x50, 0x05];
+    let now = Cell::new(0);
+    for policy in [
+        SamplingPolicy::Cached,
+        SamplingPolicy::Reject,
+        SamplingPolicy::Wait,
+    ] {
+        now.set(0);
+        let mut am2320 = Am2320::new_nonblocking(mock::I2c::new(&[response]), mock::Clock(&now))
+            .with_sampling_policy(policy);
+        assert_eq!(am2320.finish_measurement(), Err(nb::Error::WouldBlock));
+        now.set(900);
+        assert_eq!(am2320.finish_measurement(), Err(nb::Error::WouldBlock));
+        now.set(2500);
+        let measurement = am2320.finish_measurement().unwrap();
+
+        now.set(500_000);
+        match policy {
+            SamplingPolicy::Cached => {
+                am2320.start_measurement().unwrap();
+                assert_eq!(am2320.finish_measurement(), Ok(measurement));
+            }
+            SamplingPolicy::Reject => {
+                let too_soon = Error::TooSoon { wait_ms: 1503 };
+                assert_eq!(am2320.start_measurement(), Err(too_soon.clone()));
+                assert_eq!(am2320.finish_measurement(), Err(nb::Error::Other(too_soon)));
+            }
+            SamplingPolicy::Wait => {
+                am2320.start_measurement().unwrap();
+                assert_eq!(am2320.finish_measurement(), Err(nb::Error::WouldBlock));
+            }
+        }
+        assert!(!am2320.is_measuring());
+        assert_eq!(am2320.device.writes.len(), 2);
+
+        // Once the interval elapsed, a new measurement starts
+        now.set(2500 + crate::MIN_SAMPLING_INTERVAL_US);
+        assert_eq!(am2320.finish_measurement(), Err(nb::Error::WouldBlock));
+        assert_eq!(am2320.device.writes.len(), 3);
+    }
+}
+
+#[test]
+fn test_blocking_transfer() {
+    use crate::mock;
+    use core::cell::Cell;
+
+    let registers: &[u8] = &[0x03, 0x02, 0x00, 0x00, 0xa1, 0xa0];
+    let now = Cell::new(0);
+    let mut am2320 = Am2320::new(mock::I2c::new(&[registers]), mock::Clock(&now))
+        .with_clock(mock::Clock(&now), SamplingPolicy::Reject);
+    am2320.start_measurement().unwrap();
+    now.set(900);
+    assert_eq!(am2320.finish_measurement(), Err(nb::Error::WouldBlock));
+
+    // The blocking read answers its own command and abandons the pending one
+    am2320.read_registers(0x10, &mut [0; 2]).unwrap();
+    assert!(!am2320.is_measuring());
+    assert_eq!(am2320.finish_measurement(), Err(nb::Error::WouldBlock));
+    assert_eq!(am2320.device.writes.len(), 5);
+    assert_eq!(am2320.device.writes[4], [0x00]);
+}
+
+#[cfg(feature = "sim")]
+#[test]
+fn test_late_polls() {
+    use crate::sim::{SimTime, SimulatedAm2320};
+
+    let time = SimTime::new();
+    let mut sensor = SimulatedAm2320::new(time.clone());
+    let mut am2320 = Am2320::new_nonblocking(&mut sensor, time.clone());
+
+    // Polling every 5 ms misses every wake window, without failing
+    for _ in 0..10 {
+        assert_eq!(am2320.finish_measurement(), Err(nb::Error::WouldBlock));
+        time.advance_us(5000);
+    }
+
+    // A late read restarts the measurement as well
+    assert_eq!(am2320.finish_measurement(), Err(nb::Error::WouldBlock));
+    time.advance_us(1000);
+    assert_eq!(am2320.finish_measurement(), Err(nb::Error::WouldBlock));
+    time.advance_us(5000);
+    assert_eq!(am2320.finish_measurement(), Err(nb::Error::WouldBlock));
+
+    time.advance_us(1000);
+    assert_eq!(am2320.finish_measurement(), Err(nb::Error::WouldBlock));
+    time.advance_us(2000);
+    let measurement = am2320.finish_measurement().unwrap();
+    assert_eq!(measurement.humidity_permille, 500);
+    assert_eq!(sensor.commands(), 2);
+}
@@ -0,0 +1,326 @@
+//! Transport-independent encoding and decoding of the sensor frames
+//!
+//! The AM2320 speaks a subset of Modbus over I2C: the host sends a request
+//! (function code, start address, number of registers and, for writes, the
+//! values followed by a CRC), and reads back a response ending with a CRC.
+//! Nothing in this module touches a bus, so it can be reused over any
+//! transport, on captured frames or in tests.
+//!
+//! ```
+//! use am2320::protocol::{decode_measurement, decode_response, encode_read_request, Response};
+//!
+//! assert_eq!(encode_read_request(0x00, 4), Ok([0x03, 0x00, 0x04]));
+//!
+//! let frame = [0x03, 0x04, 0x02, 0x36, 0x80, 0x65, 0xb1, 0xb5];
+//! let registers = [0x02, 0x36, 0x80, 0x65];
+//! assert_eq!(decode_response(&frame), Ok(Response::Read(&registers[..])));
+//! let measurement = decode_measurement(&registers);
+//! assert_eq!(measurement.temperature_decicelsius, -101);
+//! ```
+
+use core::fmt;
+
+use crate::{DeviceInfo, ModbusException, RawMeasurement, MAX_REGISTERS, REGISTER_MAP_SIZE};
+
+/// Modbus function code to read registers
+pub const READ_REGISTERS: u8 = 0x03;
+/// Modbus function code to write registers
+pub const WRITE_REGISTERS: u8 = 0x10;
+/// Length of the longest frame, a write request of `MAX_REGISTERS` registers
+pub const MAX_FRAME_LEN: usize = MAX_REGISTERS + 5;
+
+/// Errors while encoding or decoding a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FrameError {
+    /// The registers are outside of the register map, or more than
+    /// `MAX_REGISTERS` of them were requested
+    InvalidRegisterRange,
+    /// The frame is shorter than its header announces
+    Truncated,
+    /// The function code isn't one the sensor sends
+    UnexpectedHeader,
+    /// The CRC doesn't match the frame
+    CrcMismatch {
+        /// CRC computed from the frame
+        expected: u16,
+        /// CRC trailing the frame
+        actual: u16,
+    },
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::InvalidRegisterRange => f.write_str("invalid register range"),
+            FrameError::Truncated => f.write_str("truncated frame"),
+            FrameError::UnexpectedHeader => f.write_str("unexpected response header"),
+            FrameError::CrcMismatch { expected, actual } => write!(
+                f,
+                "CRC mismatch, expected {:#06x} but the frame holds {:#06x}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl core::error::Error for FrameError {}
+
+/// Response decoded by `decode_response`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Response<'a> {
+    /// Values of the registers read
+    Read(&'a [u8]),
+    /// Acknowledgement of a write
+    Write {
+        /// Address of the first register written
+        start: u8,
+        /// Number of registers written
+        len: u8,
+    },
+    /// The sensor refused the command
+    Exception {
+        /// Function code of the refused command
+        function: u8,
+        /// Reason of the refusal
+        exception: ModbusException,
+    },
+}
+
+/// Computes the Modbus CRC of `data`, sent lsb first after the frame
+#[inline(always)]
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for e in data.iter() {
+        crc ^= u16::from(*e);
+        for _ in 0..8 {
+            if crc & 0x0001 == 0x0001 {
+                crc >>= 1;
+                crc ^= 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
+
+/// Checks that `len` registers starting at `start` can be accessed at once
+fn check_register_range(start: u8, len: usize) -> Result<(), FrameError> {
+    if len == 0 || len > MAX_REGISTERS || usize::from(start) + len > REGISTER_MAP_SIZE {
+        return Err(FrameError::InvalidRegisterRange);
+    }
+    Ok(())
+}
+
+/// Builds the request reading `len` registers starting at `start`
+///
+/// Read requests don't carry a CRC.
+pub fn encode_read_request(start: u8, len: usize) -> Result<[u8; 3], FrameError> {
+    check_register_range(start, len)?;
+    Ok([READ_REGISTERS, start, len as u8])
+}
+
+/// Builds the request writing `data` starting at `start` into `buffer`
+///
+/// The request is made of the function code, the start address, the number
+/// of registers, the register values and the CRC (lsb first).
+pub fn encode_write_request<'a>(
+    start: u8,
+    data: &[u8],
+    buffer: &'a mut [u8; MAX_FRAME_LEN],
+) -> Result<&'a [u8], FrameError> {
+    check_register_range(start, data.len())?;
+
+    let n = data.len() + 3;
+    buffer[0] = WRITE_REGISTERS;
+    buffer[1] = start;
+    buffer[2] = data.len() as u8;
+    buffer[3..n].copy_from_slice(data);
+    let crc = crc16(&buffer[0..n]);
+    buffer[n..n + 2].copy_from_slice(&crc.to_le_bytes());
+
+    Ok(&buffer[..n + 2])
+}
+
+/// Checks the CRC trailing the first `n` bytes of `frame`
+fn check_crc(frame: &[u8], n: usize) -> Result<(), FrameError> {
+    let crc = frame.get(n..n + 2).ok_or(FrameError::Truncated)?;
+    let expected = crc16(&frame[0..n]);
+    let actual = u16::from_le_bytes([crc[0], crc[1]]);
+    if expected != actual {
+        return Err(FrameError::CrcMismatch { expected, actual });
+    }
+    Ok(())
+}
+
+/// Decodes a response read from the sensor
+///
+/// Read response:
+/// byte 0: Modbus function code 0x03
+/// byte 1: Number of registers read
+/// byte 2..n: Register values
+/// byte n: CRC lsb byte
+/// byte n + 1: CRC msb byte
+///
+/// Write response:
+/// byte 0: Modbus function code 0x10
+/// byte 1: Start address
+/// byte 2: Number of registers written
+/// byte 3: CRC lsb byte
+/// byte 4: CRC msb byte
+///
+/// Exception response:
+/// byte 0: Modbus function code with its msb set (`function | 0x80`)
+/// byte 1: Exception code
+/// byte 2: CRC lsb byte
+/// byte 3: CRC msb byte
+///
+/// Bytes past the end of the response are ignored, since the length of the
+/// response isn't known before reading it.
+pub fn decode_response(frame: &[u8]) -> Result<Response<'_>, FrameError> {
+    let (&function, header) = frame.split_first().ok_or(FrameError::Truncated)?;
+    let &len = header.first().ok_or(FrameError::Truncated)?;
+    match function {
+        READ_REGISTERS => {
+            let n = usize::from(len) + 2;
+            check_crc(frame, n)?;
+            Ok(Response::Read(&frame[2..n]))
+        }
+        WRITE_REGISTERS => {
+            check_crc(frame, 3)?;
+            Ok(Response::Write {
+                start: frame[1],
+                len: frame[2],
+            })
+        }
+        _ if function & 0x80 != 0 => {
+            check_crc(frame, 2)?;
+            Ok(Response::Exception {
+                function: function & 0x7F,
+                exception: frame[1].into(),
+            })
+        }
+        _ => Err(FrameError::UnexpectedHeader),
+    }
+}
+
+/// Decodes registers 0x00 to 0x03 into a `RawMeasurement`
+///
+/// byte 0: Humidity msb
+/// byte 1: Humidity lsb
+/// byte 2: Temperature msb, the msb is the sign bit
+/// byte 3: Temperature lsb
+pub fn decode_measurement(data: &[u8; 4]) -> RawMeasurement {
+    let mut temperature = i16::from_be_bytes([data[2] & 0b0111_1111, data[3]]);
+    if data[2] & 0b1000_0000 != 0 {
+        temperature = -temperature;
+    }
+
+    let humidity = u16::from_be_bytes([data[0], data[1]]);
+
+    RawMeasurement {
+        humidity_permille: humidity,
+        temperature_decicelsius: temperature,
+    }
+}
+
+/// Encodes a `RawMeasurement` into registers 0x00 to 0x03
+///
+/// This is the inverse of `decode_measurement`.
+pub fn encode_measurement(measurement: &RawMeasurement) -> [u8; 4] {
+    let [humidity_msb, humidity_lsb] = measurement.humidity_permille.to_be_bytes();
+    let temperature = measurement.temperature_decicelsius;
+    let [mut temperature_msb, temperature_lsb] = temperature.unsigned_abs().to_be_bytes();
+    if temperature < 0 {
+        temperature_msb |= 0b1000_0000;
+    }
+    [humidity_msb, humidity_lsb, temperature_msb, temperature_lsb]
+}
+
+/// Decodes registers 0x08 to 0x0E into a `DeviceInfo`
+///
+/// byte 0: Model msb
+/// byte 1: Model lsb
+/// byte 2: Version number
+/// byte 3..7: Device ID, msb first
+pub fn decode_device_info(data: &[u8; 7]) -> DeviceInfo {
+    DeviceInfo {
+        model: u16::from_be_bytes([data[0], data[1]]),
+        version: data[2],
+        id: u32::from_be_bytes([data[3], data[4], data[5], data[6]]),
+    }
+}
+
+#[test]
+fn test_crc16() {
+    assert_eq!(crc16(&[]), 0xFFFF);
+    assert_eq!(crc16(&[0x03, 0x04, 0x02, 0x36, 0x0, 0xDB]), 0x0550);
+}
+
+#[test]
+fn test_requests() {
+    assert_eq!(encode_read_request(0x08, 7), Ok([0x03, 0x08, 0x07]));
+    assert_eq!(
+        encode_read_request(0x1f, 2),
+        Err(FrameError::InvalidRegisterRange)
+    );
+
+    let mut buffer = [0; MAX_FRAME_LEN];
+    assert_eq!(
+        encode_write_request(0x10, &[0x12, 0x34], &mut buffer),
+        Ok(&[0x10, 0x10, 0x02, 0x12, 0x34, 0x4d, 0xb4][..])
+    );
+    assert_eq!(
+        encode_write_request(0x10, &[0; 11], &mut buffer),
+        Err(FrameError::InvalidRegisterRange)
+    );
+}
+
+#[test]
+fn test_decode_response() {
+    let frame = [0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x50, 0x05, 0xff];
+    assert_eq!(
+        decode_response(&frame),
+        Ok(Response::Read(&[0x02, 0x36, 0x00, 0xdb]))
+    );
+    assert_eq!(
+        decode_response(&[0x10, 0x10, 0x02, 0xfc, 0x04]),
+        Ok(Response::Write {
+            start: 0x10,
+            len: 0x02
+        })
+    );
+    assert_eq!(
+        decode_response(&[0x90, 0x84, 0x6d, 0xd3]),
+        Ok(Response::Exception {
+            function: WRITE_REGISTERS,
+            exception: ModbusException::WriteDisabled,
+        })
+    );
+
+    assert_eq!(
+        decode_response(&[0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x50]),
+        Err(FrameError::Truncated)
+    );
+    assert_eq!(
+        decode_response(&[0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x00, 0x00]),
+        Err(FrameError::CrcMismatch {
+            expected: 0x0550,
+            actual: 0x0000
+        })
+    );
+    assert_eq!(
+        decode_response(&[0x04, 0x00, 0x00, 0x00]),
+        Err(FrameError::UnexpectedHeader)
+    );
+}
+
+#[test]
+fn test_encode_measurement() {
+    for data in [[0x02, 0x36, 0x80, 0x65], [0x03, 0xe7, 0x03, 0x20]] {
+        assert_eq!(encode_measurement(&decode_measurement(&data)), data);
+    }
+}
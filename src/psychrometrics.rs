@@ -0,0 +1,158 @@
+//! Quantities derived from the temperature and the relative humidity
+
+use libm::{atanf, expf, logf, powf, sqrtf};
+
+use crate::{Measurement, Temperature};
+
+/// Magnus formula coefficients over water (Sonntag 1990)
+const MAGNUS_A: f32 = 17.62;
+const MAGNUS_B: f32 = 243.12;
+
+/// Lowest relative humidity used by `dew_point`, the sensor resolution
+/// (0.1 %), since the Magnus formula diverges at 0 %
+const MIN_HUMIDITY_RATIO: f32 = 0.001;
+
+impl Measurement {
+    /// Dew point, computed with the Magnus formula
+    ///
+    /// The formula has no finite result at 0 %, so humidities below the
+    /// sensor resolution are computed as 0.1 %, which the sensor can't tell
+    /// apart from 0 % anyway.
+    pub fn dew_point(&self) -> Temperature {
+        let t = self.temperature.celsius();
+        let rh = self.humidity.ratio().max(MIN_HUMIDITY_RATIO);
+        let gamma = logf(rh) + MAGNUS_A * t / (MAGNUS_B + t);
+        Temperature::from_celsius(MAGNUS_B * gamma / (MAGNUS_A - gamma))
+    }
+
+    /// Absolute humidity in grams of water vapour per cubic meter (g/m³)
+    pub fn absolute_humidity(&self) -> f32 {
+        let t = self.temperature.celsius();
+        6.112 * expf(17.67 * t / (t + 243.5)) * self.humidity.percent() * 2.1674
+            / self.temperature.kelvin()
+    }
+
+    /// Heat index, computed with the NOAA Rothfusz regression and its
+    /// adjustments
+    pub fn heat_index(&self) -> Temperature {
+        let t = self.temperature.fahrenheit();
+        let rh = self.humidity.percent();
+
+        let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+        let hi = if (simple + t) / 2.0 < 80.0 {
+            simple
+        } else {
+            let mut hi = -42.379 + 2.049_015_3 * t + 10.143_331 * rh
+                - 0.224_755_4 * t * rh
+                - 0.006_837_83 * t * t
+                - 0.054_817_17 * rh * rh
+                + 0.001_228_74 * t * t * rh
+                + 0.000_852_82 * t * rh * rh
+                - 0.000_001_99 * t * t * rh * rh;
+            if rh < 13.0 && (80.0..=112.0).contains(&t) {
+                hi -= (13.0 - rh) / 4.0 * sqrtf((17.0 - (t - 95.0).abs()) / 17.0);
+            } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+                hi += (rh - 85.0) / 10.0 * ((87.0 - t) / 5.0);
+            }
+            hi
+        };
+        Temperature::from_celsius((hi - 32.0) / 1.8)
+    }
+
+    /// Humidex, as defined by Environment Canada
+    pub fn humidex(&self) -> f32 {
+        let vapour_pressure =
+            6.11 * expf(5417.753 * (1.0 / 273.16 - 1.0 / self.dew_point().kelvin()));
+        self.temperature.celsius() + 0.5555 * (vapour_pressure - 10.0)
+    }
+
+    /// Vapour pressure deficit in kilopascals (kPa)
+    pub fn vapour_pressure_deficit(&self) -> f32 {
+        let t = self.temperature.celsius();
+        let saturation = 0.61078 * expf(17.27 * t / (t + 237.3));
+        saturation * (1.0 - self.humidity.ratio())
+    }
+
+    /// Wet-bulb temperature, computed with the Stull (2011) formula
+    ///
+    /// The formula is valid between -20 °C and 50 °C, and between 5 % and
+    /// 99 % relative humidity.
+    pub fn wet_bulb(&self) -> Temperature {
+        let t = self.temperature.celsius();
+        let rh = self.humidity.percent();
+        Temperature::from_celsius(
+            t * atanf(0.151_977 * sqrtf(rh + 8.313_659)) + atanf(t + rh) - atanf(rh - 1.676_331)
+                + 0.003_918_38 * powf(rh, 1.5) * atanf(0.023_101 * rh)
+                - 4.686_035,
+        )
+    }
+}
+
+#[cfg(test)]
+fn measurement(celsius: f32, percent: f32) -> Measurement {
+    Measurement {
+        temperature: Temperature::from_celsius(celsius),
+        humidity: crate::RelativeHumidity::from_percent(percent),
+    }
+}
+
+#[cfg(test)]
+fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "{} is not within {} of {}",
+        actual,
+        tolerance,
+        expected
+    );
+}
+
+#[test]
+fn test_dew_point() {
+    assert_close(measurement(25.0, 60.0).dew_point().celsius(), 16.7, 0.05);
+    assert_close(measurement(10.0, 100.0).dew_point().celsius(), 10.0, 0.01);
+    // 0 % is a valid reading, computed as 0.1 %
+    let dry = measurement(25.0, 0.0);
+    assert_close(dry.dew_point().celsius(), -55.9, 0.05);
+    assert!(dry.humidex().is_finite());
+}
+
+#[test]
+fn test_absolute_humidity() {
+    assert_close(measurement(25.0, 50.0).absolute_humidity(), 11.5, 0.05);
+}
+
+#[test]
+fn test_heat_index() {
+    // NOAA heat index chart: 90 °F at 60 % feels like 100 °F
+    let hot = measurement((90.0 - 32.0) / 1.8, 60.0);
+    assert_close(hot.heat_index().fahrenheit(), 100.0, 0.5);
+    // Below 80 °F the heat index stays close to the temperature
+    assert_close(measurement(20.0, 50.0).heat_index().celsius(), 19.4, 0.05);
+}
+
+#[test]
+fn test_humidex() {
+    // Environment Canada humidex table: 30 °C with a 15 °C dew point gives 34
+    assert_close(measurement(30.0, 40.19).humidex(), 34.0, 0.05);
+}
+
+#[test]
+fn test_vapour_pressure_deficit() {
+    assert_close(
+        measurement(25.0, 50.0).vapour_pressure_deficit(),
+        1.58,
+        0.005,
+    );
+    assert_close(
+        measurement(25.0, 100.0).vapour_pressure_deficit(),
+        0.0,
+        1e-6,
+    );
+}
+
+#[test]
+fn test_wet_bulb() {
+    // Stull (2011): 20 °C at 50 % gives a 13.7 °C wet-bulb temperature
+    assert_close(measurement(20.0, 50.0).wet_bulb().celsius(), 13.7, 0.05);
+}
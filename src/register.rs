@@ -0,0 +1,91 @@
+//! Register map of the sensor
+
+/// Registers of the sensor, named after the datasheet
+///
+/// Each register spans `size()` consecutive addresses starting at
+/// `address()`, multi-byte values are stored msb first. Addresses 0x04 to
+/// 0x07 and 0x14 to 0x1F are reserved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Register {
+    /// High byte of the humidity (0x00)
+    HumidityHigh = 0x00,
+    /// Low byte of the humidity (0x01)
+    HumidityLow = 0x01,
+    /// High byte of the temperature, its msb is the sign bit (0x02)
+    TemperatureHigh = 0x02,
+    /// Low byte of the temperature (0x03)
+    TemperatureLow = 0x03,
+    /// Model number (0x08 and 0x09)
+    Model = 0x08,
+    /// Version number (0x0A)
+    Version = 0x0A,
+    /// 32-bit device ID (0x0B to 0x0E)
+    DeviceId = 0x0B,
+    /// Status register (0x0F)
+    Status = 0x0F,
+    /// User register 1 (0x10 and 0x11)
+    UserReg1 = 0x10,
+    /// User register 2 (0x12 and 0x13)
+    UserReg2 = 0x12,
+}
+
+impl Register {
+    /// Every register, in address order
+    pub const ALL: [Register; 10] = [
+        Register::HumidityHigh,
+        Register::HumidityLow,
+        Register::TemperatureHigh,
+        Register::TemperatureLow,
+        Register::Model,
+        Register::Version,
+        Register::DeviceId,
+        Register::Status,
+        Register::UserReg1,
+        Register::UserReg2,
+    ];
+
+    /// Address of the first byte of the register
+    pub const fn address(self) -> u8 {
+        self as u8
+    }
+
+    /// Number of bytes of the register
+    pub const fn size(self) -> usize {
+        match self {
+            Register::Model | Register::UserReg1 | Register::UserReg2 => 2,
+            Register::DeviceId => 4,
+            _ => 1,
+        }
+    }
+
+    /// Returns `true` if the register can be written, only the user
+    /// registers can
+    pub const fn is_writable(self) -> bool {
+        matches!(self, Register::UserReg1 | Register::UserReg2)
+    }
+
+    /// Returns the register holding `address`, `None` for reserved addresses
+    pub fn at(address: u8) -> Option<Register> {
+        Register::ALL.iter().copied().find(|register| {
+            (register.address()..register.address() + register.size() as u8).contains(&address)
+        })
+    }
+}
+
+#[test]
+fn test_register_map() {
+    assert_eq!(Register::at(0x00), Some(Register::HumidityHigh));
+    assert_eq!(Register::at(0x05), None);
+    assert_eq!(Register::at(0x09), Some(Register::Model));
+    assert_eq!(Register::at(0x0E), Some(Register::DeviceId));
+    assert_eq!(Register::at(0x13), Some(Register::UserReg2));
+    assert_eq!(Register::at(0x14), None);
+    assert!(Register::UserReg1.is_writable());
+    assert!(!Register::Status.is_writable());
+    for register in Register::ALL {
+        assert_eq!(Register::at(register.address()), Some(register));
+    }
+}
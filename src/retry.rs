@@ -0,0 +1,38 @@
+//! Retrying transfers that failed because of transient errors
+
+/// How transfers failing with a transient error are retried
+///
+/// Bus errors, CRC mismatches and unexpected headers are usually caused by
+/// noise on long cables and go away when the transfer is repeated. Modbus
+/// exceptions are never retried since the sensor would refuse the command
+/// again, see [`Error::is_transient`](crate::Error::is_transient).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one
+    pub attempts: u8,
+    /// Time to wait before each new attempt, in microseconds
+    pub backoff_us: u32,
+}
+
+impl RetryPolicy {
+    /// Makes a single attempt, this is the default
+    pub const fn none() -> Self {
+        Self::new(1, 0)
+    }
+
+    /// Makes at most `attempts` attempts, waiting `backoff_us` in-between
+    pub const fn new(attempts: u8, backoff_us: u32) -> Self {
+        Self {
+            attempts,
+            backoff_us,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
@@ -0,0 +1,293 @@
+//! Continuous sampling with smoothing filters
+
+use embedded_hal::{delay, i2c};
+
+use crate::{Am2320, Clock, Error, RawMeasurement, MIN_SAMPLING_INTERVAL_US};
+
+/// Smoothing applied by a `Sampler` to the measurements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Filter {
+    /// Mean of the last `N` measurements
+    MovingAverage,
+    /// Exponential moving average, each new measurement weighting
+    /// `alpha_percent` % (1 to 100) of the result
+    Exponential {
+        /// Weight of the new measurement, in percents
+        alpha_percent: u8,
+    },
+    /// Median of the last `N` measurements, which ignores isolated outliers
+    Median,
+}
+
+/// Collects measurements at the legal rate and smoothes them
+///
+/// The last `N` measurements are kept in a fixed-size buffer, so smoothing
+/// doesn't need an allocator. The sensor's clock, set with
+/// [`Am2320::with_clock`], tells when the next measurement is due, so a
+/// sensor without one can't be sampled:
+///
+/// ```compile_fail
+/// # fn sample<I2C: embedded_hal::i2c::I2c, Delay: embedded_hal::delay::DelayNs>(
+/// #     am2320: am2320::Am2320<I2C, Delay>,
+/// # ) {
+/// let sampler = am2320::Sampler::<_, _, _, 4>::new(am2320, am2320::Filter::Median);
+/// # }
+/// ```
+pub struct Sampler<I2C, Delay, C, const N: usize> {
+    /// Sensor the measurements are read from
+    sensor: Am2320<I2C, Delay, C>,
+    /// Smoothing applied to the measurements
+    filter: Filter,
+    /// Ring buffer of the last measurements
+    samples: [RawMeasurement; N],
+    /// Number of measurements in `samples`
+    len: usize,
+    /// Index of the next measurement in `samples`
+    next: usize,
+    /// Exponential moving average of the temperature and humidity, in
+    /// hundredths of their raw units
+    average: Option<(i32, i32)>,
+    /// Time the last measurement ended at
+    last_poll: Option<u64>,
+}
+
+impl<I2C, Delay, C, E, const N: usize> Sampler<I2C, Delay, C, N>
+where
+    I2C: i2c::I2c<Error = E>,
+    Delay: delay::DelayNs,
+    C: Clock,
+{
+    /// Samples `sensor`, smoothing the last `N` measurements with `filter`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is 0 or if `alpha_percent` isn't between 1 and 100.
+    pub fn new(sensor: Am2320<I2C, Delay, C>, filter: Filter) -> Self {
+        assert!(N > 0, "the sampler needs room for at least one measurement");
+        if let Filter::Exponential { alpha_percent } = filter {
+            assert!(
+                (1..=100).contains(&alpha_percent),
+                "alpha must be 1 to 100 %"
+            );
+        }
+        Self {
+            sensor,
+            filter,
+            samples: [RawMeasurement {
+                humidity_permille: 0,
+                temperature_decicelsius: 0,
+            }; N],
+            len: 0,
+            next: 0,
+            average: None,
+            last_poll: None,
+        }
+    }
+
+    /// Destroys the sampler and returns the sensor
+    pub fn release(self) -> Am2320<I2C, Delay, C> {
+        self.sensor
+    }
+
+    /// Reads a new measurement if `MIN_SAMPLING_INTERVAL_US` elapsed since
+    /// the last one
+    ///
+    /// Returns the smoothed value when a measurement was made and `None` when
+    /// it is too soon.
+    pub fn poll(&mut self) -> Result<Option<RawMeasurement>, Error<E>> {
+        if let Some(at) = self.last_poll {
+            if self.sensor.clock.now_us().saturating_sub(at) < MIN_SAMPLING_INTERVAL_US {
+                return Ok(None);
+            }
+        }
+        self.measure().map(Some)
+    }
+
+    /// Waits until the next measurement is due, reads it and returns the
+    /// smoothed value
+    pub fn sample(&mut self) -> Result<RawMeasurement, Error<E>> {
+        if let Some(at) = self.last_poll {
+            let elapsed = self.sensor.clock.now_us().saturating_sub(at);
+            if elapsed < MIN_SAMPLING_INTERVAL_US {
+                let wait_us = MIN_SAMPLING_INTERVAL_US - elapsed;
+                self.sensor.delay.delay_us(wait_us as u32);
+            }
+        }
+        self.measure()
+    }
+
+    /// Returns the smoothed value, `None` before the first measurement
+    pub fn value(&self) -> Option<RawMeasurement> {
+        if self.len == 0 {
+            return None;
+        }
+        let samples = &self.samples[..self.len];
+        Some(match self.filter {
+            Filter::MovingAverage => {
+                let (temperature, humidity) = samples.iter().fold((0, 0), |(t, h), sample| {
+                    (
+                        t + i32::from(sample.temperature_decicelsius),
+                        h + i32::from(sample.humidity_permille),
+                    )
+                });
+                let len = self.len as i32;
+                raw(div_round(temperature, len), div_round(humidity, len))
+            }
+            Filter::Exponential { .. } => {
+                let (temperature, humidity) = self.average?;
+                raw(div_round(temperature, 100), div_round(humidity, 100))
+            }
+            Filter::Median => {
+                let mut temperatures = [0; N];
+                let mut humidities = [0; N];
+                for (i, sample) in samples.iter().enumerate() {
+                    temperatures[i] = i32::from(sample.temperature_decicelsius);
+                    humidities[i] = i32::from(sample.humidity_permille);
+                }
+                raw(
+                    median(&mut temperatures[..self.len]),
+                    median(&mut humidities[..self.len]),
+                )
+            }
+        })
+    }
+
+    /// Returns the smoothed value as a `Measurement`
+    #[cfg(feature = "float")]
+    pub fn measurement(&self) -> Option<crate::Measurement> {
+        self.value().map(crate::Measurement::from)
+    }
+
+    /// Forgets every measurement made so far
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.next = 0;
+        self.average = None;
+    }
+
+    /// Reads a measurement and adds it to the filter
+    fn measure(&mut self) -> Result<RawMeasurement, Error<E>> {
+        let result = self.sensor.read_raw();
+        // A failed measurement still woke the sensor up
+        self.last_poll = Some(self.sensor.clock.now_us());
+        let measurement = result?;
+
+        self.samples[self.next] = measurement;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+
+        let temperature = i32::from(measurement.temperature_decicelsius) * 100;
+        let humidity = i32::from(measurement.humidity_permille) * 100;
+        self.average = Some(match (self.filter, self.average) {
+            (Filter::Exponential { alpha_percent }, Some((t, h))) => {
+                let alpha = i32::from(alpha_percent);
+                (
+                    t + div_round((temperature - t) * alpha, 100),
+                    h + div_round((humidity - h) * alpha, 100),
+                )
+            }
+            _ => (temperature, humidity),
+        });
+
+        // `value` is only `None` before the first measurement
+        Ok(self.value().unwrap_or(measurement))
+    }
+}
+
+/// Divides `n` by `d`, rounding half away from zero
+fn div_round(n: i32, d: i32) -> i32 {
+    if n >= 0 {
+        (n + d / 2) / d
+    } else {
+        (n - d / 2) / d
+    }
+}
+
+/// Median of `values`, the mean of the two middle ones for an even length
+fn median(values: &mut [i32]) -> i32 {
+    values.sort_unstable();
+    let middle = values.len() / 2;
+    if values.len() % 2 == 0 {
+        div_round(values[middle - 1] + values[middle], 2)
+    } else {
+        values[middle]
+    }
+}
+
+/// Builds a `RawMeasurement` from values derived from one
+fn raw(temperature: i32, humidity: i32) -> RawMeasurement {
+    RawMeasurement {
+        humidity_permille: humidity as u16,
+        temperature_decicelsius: temperature as i16,
+    }
+}
+
+#[cfg(test)]
+fn new_sampler<'a, const N: usize>(
+    now: &'a core::cell::Cell<u64>,
+    humidities: &[u16],
+    filter: Filter,
+) -> Sampler<crate::mock::I2c, crate::mock::Clock<'a>, crate::mock::Clock<'a>, N> {
+    extern crate std;
+    use crate::{mock, protocol::crc16, SamplingPolicy};
+    use std::vec::Vec;
+
+    let responses: Vec<Vec<u8>> = humidities
+        .iter()
+        .map(|humidity| {
+            let mut response = Vec::from([0x03, 0x04]);
+            response.extend_from_slice(&humidity.to_be_bytes());
+            response.extend_from_slice(&[0x00, 0xdb]);
+            let crc = crc16(&response);
+            response.extend_from_slice(&crc.to_le_bytes());
+            response
+        })
+        .collect();
+    let responses: Vec<&[u8]> = responses.iter().map(Vec::as_slice).collect();
+    let sensor = Am2320::new(mock::I2c::new(&responses), mock::Clock(now))
+        .with_clock(mock::Clock(now), SamplingPolicy::Reject);
+    Sampler::new(sensor, filter)
+}
+
+#[test]
+fn test_rate() {
+    let now = core::cell::Cell::new(0);
+    let mut sampler = new_sampler::<4>(&now, &[500, 510, 520], Filter::MovingAverage);
+    assert_eq!(sampler.value(), None);
+    assert_eq!(sampler.poll().unwrap().unwrap().humidity_permille, 500);
+    assert_eq!(sampler.poll(), Ok(None));
+    now.set(now.get() + MIN_SAMPLING_INTERVAL_US);
+    assert_eq!(sampler.poll().unwrap().unwrap().humidity_permille, 505);
+    // Waits for the sensor instead of being rejected by it
+    assert_eq!(sampler.sample().unwrap().humidity_permille, 510);
+    assert_eq!(sampler.value().unwrap().temperature_decicelsius, 219);
+}
+
+#[test]
+fn test_filters() {
+    let now = core::cell::Cell::new(0);
+    let humidities = [500, 510, 900, 520, 530];
+
+    let mut sampler = new_sampler::<3>(&now, &humidities, Filter::MovingAverage);
+    let averages = [500, 505, 637, 643, 650];
+    for average in averages {
+        assert_eq!(sampler.sample().unwrap().humidity_permille, average);
+    }
+
+    let mut sampler = new_sampler::<3>(&now, &humidities, Filter::Median);
+    let medians = [500, 505, 510, 520, 530];
+    for median in medians {
+        assert_eq!(sampler.sample().unwrap().humidity_permille, median);
+    }
+
+    let filter = Filter::Exponential { alpha_percent: 20 };
+    let mut sampler = new_sampler::<1>(&now, &humidities, filter);
+    let averages = [500, 502, 582, 569, 561];
+    for average in averages {
+        assert_eq!(sampler.sample().unwrap().humidity_permille, average);
+    }
+    sampler.reset();
+    assert_eq!(sampler.value(), None);
+}
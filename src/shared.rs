@@ -0,0 +1,138 @@
+//! Constructors sharing the I2C bus with other devices
+//!
+//! The driver only holds the bus for the duration of each transaction, so the
+//! NACK'ed wake-up write simply releases it again: the `RefCell` borrow, the
+//! critical section or the atomic flag is dropped like after any other error
+//! and the other devices on the bus are unaffected.
+
+#[cfg(feature = "embedded-hal-bus")]
+use core::cell::RefCell;
+
+#[cfg(feature = "embedded-hal-bus")]
+use embedded_hal::{delay, i2c};
+#[cfg(feature = "embedded-hal-bus")]
+use embedded_hal_bus::i2c::{CriticalSectionDevice, RefCellDevice};
+#[cfg(all(feature = "embedded-hal-bus", target_has_atomic = "8"))]
+use embedded_hal_bus::{i2c::AtomicDevice, util::AtomicCell};
+
+#[cfg(feature = "embedded-hal-bus")]
+use crate::Am2320;
+
+#[cfg(feature = "embedded-hal-bus")]
+impl<'a, BUS, Delay> Am2320<RefCellDevice<'a, BUS>, Delay>
+where
+    BUS: i2c::I2c,
+    Delay: delay::DelayNs,
+{
+    /// Create a AM2320 temperature sensor driver sharing a `RefCell` bus.
+    ///
+    /// This is the cheapest way to share a bus, but the driver can't be sent
+    /// to another thread or interrupt handler.
+    pub fn new_ref_cell(bus: &'a RefCell<BUS>, delay: Delay) -> Self {
+        Self::new(RefCellDevice::new(bus), delay)
+    }
+}
+
+#[cfg(feature = "embedded-hal-bus")]
+impl<'a, BUS, Delay> Am2320<CriticalSectionDevice<'a, BUS>, Delay>
+where
+    BUS: i2c::I2c,
+    Delay: delay::DelayNs,
+{
+    /// Create a AM2320 temperature sensor driver sharing a bus protected by a
+    /// `critical-section` mutex.
+    ///
+    /// Interrupts are disabled for the duration of each transaction, but not
+    /// while waiting in-between the commands.
+    pub fn new_critical_section(
+        bus: &'a critical_section::Mutex<RefCell<BUS>>,
+        delay: Delay,
+    ) -> Self {
+        Self::new(CriticalSectionDevice::new(bus), delay)
+    }
+}
+
+#[cfg(all(feature = "embedded-hal-bus", target_has_atomic = "8"))]
+impl<'a, BUS, Delay> Am2320<AtomicDevice<'a, BUS>, Delay>
+where
+    BUS: i2c::I2c,
+    Delay: delay::DelayNs,
+{
+    /// Create a AM2320 temperature sensor driver sharing an `AtomicCell` bus.
+    ///
+    /// Transactions fail with `AtomicError::Busy` instead of blocking when
+    /// another device is using the bus.
+    pub fn new_atomic(bus: &'a AtomicCell<BUS>, delay: Delay) -> Self {
+        Self::new(AtomicDevice::new(bus), delay)
+    }
+}
+
+#[cfg(feature = "embassy")]
+impl<'a, M, BUS, Delay>
+    crate::Am2320Async<embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice<'a, M, BUS>, Delay>
+where
+    M: embassy_sync::blocking_mutex::raw::RawMutex,
+    BUS: embedded_hal_async::i2c::I2c,
+    Delay: embedded_hal_async::delay::DelayNs,
+{
+    /// Create an asynchronous AM2320 temperature sensor driver sharing a bus
+    /// protected by an `embassy-sync` mutex.
+    pub fn new_shared(bus: &'a embassy_sync::mutex::Mutex<M, BUS>, delay: Delay) -> Self {
+        Self::new(
+            embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice::new(bus),
+            delay,
+        )
+    }
+}
+
+#[cfg(test)]
+const RESPONSE: &[u8] = &[0x03, 0x04, 0x02, 0x36, 0x00, 0xdb, 0x50, 0x05];
+
+#[cfg(feature = "embedded-hal-bus")]
+#[test]
+fn test_ref_cell() {
+    use crate::mock;
+
+    let bus = RefCell::new(mock::I2c::new(&[RESPONSE, RESPONSE]));
+    let mut am2320 = Am2320::new_ref_cell(&bus, mock::Delay);
+    am2320.read_raw().unwrap();
+    // The bus is free for other devices after the NACK'ed wake-up
+    assert!(bus.try_borrow_mut().is_ok());
+    am2320.read_raw().unwrap();
+}
+
+#[cfg(feature = "embedded-hal-bus")]
+#[test]
+fn test_critical_section() {
+    use crate::mock;
+
+    let bus = critical_section::Mutex::new(RefCell::new(mock::I2c::new(&[RESPONSE])));
+    let mut am2320 = Am2320::new_critical_section(&bus, mock::Delay);
+    am2320.read_raw().unwrap();
+    critical_section::with(|cs| assert_eq!(bus.borrow_ref(cs).writes.len(), 2));
+}
+
+#[cfg(all(feature = "embedded-hal-bus", target_has_atomic = "8"))]
+#[test]
+fn test_atomic() {
+    use crate::mock;
+
+    let bus = AtomicCell::new(mock::I2c::new(&[RESPONSE]));
+    let mut am2320 = Am2320::new_atomic(&bus, mock::Delay);
+    am2320.read_raw().unwrap();
+    // Another device can still lock the bus
+    let mut other = AtomicDevice::new(&bus);
+    i2c::I2c::write(&mut other, 0x50, &[0x01]).unwrap();
+}
+
+#[cfg(feature = "embassy")]
+#[test]
+fn test_embassy_mutex() {
+    use crate::{mock, Am2320Async};
+    use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+
+    let bus = Mutex::<NoopRawMutex, _>::new(mock::I2c::new(&[RESPONSE]));
+    let mut am2320 = Am2320Async::new_shared(&bus, mock::Delay);
+    mock::block_on(am2320.read_raw()).unwrap();
+    assert!(bus.try_lock().is_ok());
+}
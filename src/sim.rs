@@ -0,0 +1,463 @@
+//! Simulated AM2320 for host-side testing
+//!
+//! [`SimulatedAm2320`] implements `i2c::I2c` and behaves like the real
+//! sensor: it NACKs the wake-up write, answers Modbus commands from its
+//! register map, sends exception responses and goes back to sleep when the
+//! bus stays idle. Time is virtual and shared through [`SimTime`], which also
+//! implements `DelayNs` and `Clock` so the driver's delays advance it.
+//!
+//! ```
+//! use am2320::{sim::{SimTime, SimulatedAm2320}, Am2320, RawMeasurement};
+//!
+//! let time = SimTime::new();
+//! let mut sensor = SimulatedAm2320::new(time.clone());
+//! sensor.set_measurement(RawMeasurement {
+//!     humidity_permille: 452,
+//!     temperature_decicelsius: -53,
+//! });
+//!
+//! let mut am2320 = Am2320::new(&mut sensor, time);
+//! assert_eq!(am2320.read_raw().unwrap().temperature_decicelsius, -53);
+//! ```
+
+use std::{cell::Cell, collections::VecDeque, rc::Rc, vec::Vec};
+
+use embedded_hal::{delay, i2c};
+
+use crate::{
+    protocol::{crc16, encode_measurement, READ_REGISTERS, WRITE_REGISTERS},
+    Clock, DeviceInfo, RawMeasurement, Register, Timing, DEVICE_I2C_ADDR, MAX_REGISTERS,
+    REGISTER_MAP_SIZE,
+};
+
+/// Virtual time shared between the simulated sensor and the driver
+///
+/// Clones share the same time. Delays advance it, and it can also be moved
+/// forward explicitly with `advance_us`.
+#[derive(Debug, Clone, Default)]
+pub struct SimTime(Rc<Cell<u64>>);
+
+impl SimTime {
+    /// Starts the virtual time at 0
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current virtual time in microseconds
+    pub fn now_us(&self) -> u64 {
+        self.0.get()
+    }
+
+    /// Moves the virtual time forward
+    pub fn advance_us(&self, us: u64) {
+        self.0.set(self.0.get() + us);
+    }
+}
+
+impl Clock for SimTime {
+    fn now_us(&mut self) -> u64 {
+        self.0.get()
+    }
+}
+
+impl delay::DelayNs for SimTime {
+    fn delay_ns(&mut self, ns: u32) {
+        self.advance_us(u64::from(ns).div_ceil(1000));
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for SimTime {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.advance_us(u64::from(ns).div_ceil(1000));
+    }
+}
+
+/// Faults that can be injected in the simulated sensor
+///
+/// Faults are applied once each, in the order they were injected: a fault
+/// waits at the front of the queue until a transaction it concerns happens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The next command is not acknowledged
+    NackCommand,
+    /// The next read is not acknowledged
+    NackRead,
+    /// The CRC of the next response is corrupted
+    CorruptCrc,
+    /// The function code of the next response is wrong
+    BadHeader,
+    /// The next command is answered with this exception code
+    Exception(u8),
+    /// The next wake-up write doesn't wake the sensor up
+    StayAsleep,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Asleep,
+    /// Woken up at the given time
+    Awake(u64),
+}
+
+/// Simulated AM2320 sensor
+#[derive(Debug)]
+pub struct SimulatedAm2320 {
+    time: SimTime,
+    address: u8,
+    registers: [u8; REGISTER_MAP_SIZE],
+    state: State,
+    /// Time of the last transaction addressed to the sensor
+    last_activity: u64,
+    /// Response to the last command and the time it is ready at
+    response: Option<(Vec<u8>, u64)>,
+    faults: VecDeque<Fault>,
+    timing: Timing,
+    sleep_timeout_us: u64,
+    commands: usize,
+}
+
+impl SimulatedAm2320 {
+    /// Creates an asleep sensor answering on `DEVICE_I2C_ADDR`, measuring
+    /// 50 % and 20 °C
+    pub fn new(time: SimTime) -> Self {
+        let mut sensor = Self {
+            time,
+            address: DEVICE_I2C_ADDR,
+            registers: [0; REGISTER_MAP_SIZE],
+            state: State::Asleep,
+            last_activity: 0,
+            response: None,
+            faults: VecDeque::new(),
+            timing: Timing::datasheet(),
+            sleep_timeout_us: u64::from(Timing::MAX_WAKE_US),
+            commands: 0,
+        };
+        sensor.set_measurement(RawMeasurement {
+            humidity_permille: 500,
+            temperature_decicelsius: 200,
+        });
+        sensor.set_device_info(DeviceInfo {
+            model: 0x2320,
+            version: 0x01,
+            id: 0x1234_5678,
+        });
+        sensor
+    }
+
+    /// Answers on `address` instead of `DEVICE_I2C_ADDR`
+    pub fn with_address(mut self, address: u8) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Sets the minimum delays the driver has to wait, the datasheet ones by
+    /// default
+    pub fn with_timing(mut self, timing: Timing) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Sets how long the sensor stays awake without any transaction, 3ms by
+    /// default
+    pub fn with_sleep_timeout_us(mut self, timeout_us: u64) -> Self {
+        self.sleep_timeout_us = timeout_us;
+        self
+    }
+
+    /// Sets the values of the humidity and temperature registers
+    pub fn set_measurement(&mut self, measurement: RawMeasurement) {
+        self.registers[0..4].copy_from_slice(&encode_measurement(&measurement));
+    }
+
+    /// Sets the values of the identification registers
+    pub fn set_device_info(&mut self, info: DeviceInfo) {
+        let start = usize::from(Register::Model.address());
+        self.registers[start..start + 2].copy_from_slice(&info.model.to_be_bytes());
+        self.registers[start + 2] = info.version;
+        self.registers[start + 3..start + 7].copy_from_slice(&info.id.to_be_bytes());
+    }
+
+    /// The whole register map
+    pub fn registers(&self) -> &[u8; REGISTER_MAP_SIZE] {
+        &self.registers
+    }
+
+    /// Mutable access to the whole register map
+    pub fn registers_mut(&mut self) -> &mut [u8; REGISTER_MAP_SIZE] {
+        &mut self.registers
+    }
+
+    /// Queues a fault to inject after the already queued ones
+    pub fn inject(&mut self, fault: Fault) {
+        self.faults.push_back(fault);
+    }
+
+    /// Returns `true` if the sensor is currently awake
+    pub fn is_awake(&mut self) -> bool {
+        self.update_state();
+        self.state != State::Asleep
+    }
+
+    /// Number of commands acknowledged so far
+    pub fn commands(&self) -> usize {
+        self.commands
+    }
+
+    fn take_fault(&mut self, matches: impl Fn(Fault) -> bool) -> Option<Fault> {
+        let fault = *self.faults.front()?;
+        if matches(fault) {
+            self.faults.pop_front()
+        } else {
+            None
+        }
+    }
+
+    fn update_state(&mut self) {
+        let now = self.time.now_us();
+        if now.saturating_sub(self.last_activity) > self.sleep_timeout_us {
+            self.state = State::Asleep;
+            self.response = None;
+        }
+    }
+
+    fn on_write(&mut self, bytes: &[u8]) -> Result<(), i2c::ErrorKind> {
+        self.update_state();
+        let now = self.time.now_us();
+        match self.state {
+            State::Asleep => {
+                // The sensor wakes up on any activity, but doesn't ACK it
+                if self.take_fault(|f| f == Fault::StayAsleep).is_none() {
+                    self.state = State::Awake(now);
+                    self.last_activity = now;
+                }
+                Err(nack(i2c::NoAcknowledgeSource::Address))
+            }
+            State::Awake(since) if now - since < u64::from(self.timing.wake_us()) => {
+                Err(nack(i2c::NoAcknowledgeSource::Address))
+            }
+            State::Awake(_) if bytes.len() <= 1 => {
+                // Another wake-up write, acknowledged but ignored
+                self.last_activity = now;
+                Ok(())
+            }
+            State::Awake(_) => {
+                self.last_activity = now;
+                if self.take_fault(|f| f == Fault::NackCommand).is_some() {
+                    return Err(nack(i2c::NoAcknowledgeSource::Data));
+                }
+                let response = self.execute(bytes);
+                let ready_at = now + u64::from(self.timing.conversion_us());
+                self.response = Some((response, ready_at));
+                self.commands += 1;
+                Ok(())
+            }
+        }
+    }
+
+    fn on_read(&mut self, buffer: &mut [u8]) -> Result<(), i2c::ErrorKind> {
+        self.update_state();
+        let now = self.time.now_us();
+        if self.state == State::Asleep {
+            return Err(nack(i2c::NoAcknowledgeSource::Address));
+        }
+        match self.response.take() {
+            Some((response, ready_at)) if now >= ready_at => {
+                self.last_activity = now;
+                if self.take_fault(|f| f == Fault::NackRead).is_some() {
+                    return Err(nack(i2c::NoAcknowledgeSource::Address));
+                }
+                // The bus reads 0xFF past the end of the response
+                buffer.fill(0xFF);
+                let n = response.len().min(buffer.len());
+                buffer[..n].copy_from_slice(&response[..n]);
+                Ok(())
+            }
+            response => {
+                self.response = response;
+                Err(nack(i2c::NoAcknowledgeSource::Address))
+            }
+        }
+    }
+
+    /// Executes a Modbus command and builds the response
+    fn execute(&mut self, command: &[u8]) -> Vec<u8> {
+        let function = command.first().copied().unwrap_or(0);
+        let mut response = match self.take_fault(|f| matches!(f, Fault::Exception(_))) {
+            Some(Fault::Exception(code)) => Vec::from([function | 0x80, code]),
+            _ => match self.check_command(command) {
+                Ok(response) => response,
+                Err(code) => Vec::from([function | 0x80, code]),
+            },
+        };
+
+        if self.take_fault(|f| f == Fault::BadHeader).is_some() {
+            response[0] ^= 0x01;
+        }
+        let mut crc = crc16(&response).to_le_bytes();
+        if self.take_fault(|f| f == Fault::CorruptCrc).is_some() {
+            crc[0] ^= 0xFF;
+        }
+        response.extend_from_slice(&crc);
+        response
+    }
+
+    /// Returns the response without its CRC, or an exception code
+    fn check_command(&mut self, command: &[u8]) -> Result<Vec<u8>, u8> {
+        let (function, start, len) = match command {
+            [function, start, len, ..] => (*function, usize::from(*start), usize::from(*len)),
+            _ => return Err(0x80),
+        };
+        let range = start..start + len;
+        let in_map = len > 0 && len <= MAX_REGISTERS && range.end <= REGISTER_MAP_SIZE;
+
+        match function {
+            READ_REGISTERS => {
+                if !in_map {
+                    return Err(0x81);
+                }
+                let mut response = Vec::from([READ_REGISTERS, len as u8]);
+                response.extend_from_slice(&self.registers[range]);
+                Ok(response)
+            }
+            WRITE_REGISTERS => {
+                if command.len() != len + 5 {
+                    return Err(0x82);
+                }
+                let n = len + 3;
+                if crc16(&command[..n]) != u16::from_le_bytes([command[n], command[n + 1]]) {
+                    return Err(0x83);
+                }
+                if !in_map {
+                    return Err(0x81);
+                }
+                let writable = range
+                    .clone()
+                    .all(|address| Register::at(address as u8).is_some_and(Register::is_writable));
+                if !writable {
+                    return Err(0x84);
+                }
+                self.registers[range].copy_from_slice(&command[3..n]);
+                Ok(Vec::from([WRITE_REGISTERS, start as u8, len as u8]))
+            }
+            _ => Err(0x80),
+        }
+    }
+}
+
+fn nack(source: i2c::NoAcknowledgeSource) -> i2c::ErrorKind {
+    i2c::ErrorKind::NoAcknowledge(source)
+}
+
+impl i2c::ErrorType for SimulatedAm2320 {
+    type Error = i2c::ErrorKind;
+}
+
+impl i2c::I2c for SimulatedAm2320 {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if address != self.address {
+            return Err(nack(i2c::NoAcknowledgeSource::Address));
+        }
+        for operation in operations {
+            match operation {
+                i2c::Operation::Write(bytes) => self.on_write(bytes)?,
+                i2c::Operation::Read(buffer) => self.on_read(buffer)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::i2c::I2c for SimulatedAm2320 {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        i2c::I2c::transaction(self, address, operations)
+    }
+}
+
+#[cfg(test)]
+use crate::{Am2320, Error, ModbusException, RetryPolicy};
+
+#[test]
+fn test_read() {
+    let time = SimTime::new();
+    let mut sensor = SimulatedAm2320::new(time.clone());
+    let measurement = RawMeasurement {
+        humidity_permille: 999,
+        temperature_decicelsius: -400,
+    };
+    sensor.set_measurement(measurement);
+
+    let mut am2320 = Am2320::new(&mut sensor, time.clone());
+    assert_eq!(am2320.read_raw(), Ok(measurement));
+    assert_eq!(am2320.identify().unwrap().id, 0x1234_5678);
+    assert_eq!(am2320.read_register(Register::DeviceId), Ok(0x1234_5678));
+    assert_eq!(am2320.dump_all().unwrap()[..4], [0x03, 0xe7, 0x81, 0x90]);
+    let report = am2320.health_check().unwrap();
+    assert_eq!(report.measurement, measurement);
+    assert!(report.is_healthy());
+}
+
+#[test]
+fn test_write() {
+    let time = SimTime::new();
+    let mut sensor = SimulatedAm2320::new(time.clone());
+    let mut am2320 = Am2320::new(&mut sensor, time.clone());
+    am2320.set_user_register2(0xbeef).unwrap();
+    assert_eq!(am2320.user_register2(), Ok(0xbeef));
+    assert_eq!(
+        am2320.write_registers(0x00, &[0x00]),
+        Err(Error::ModbusException(ModbusException::WriteDisabled))
+    );
+    assert_eq!(sensor.registers()[0x12..0x14], [0xbe, 0xef]);
+}
+
+#[test]
+fn test_sleep() {
+    use embedded_hal::i2c::I2c;
+
+    let time = SimTime::new();
+    let mut sensor = SimulatedAm2320::new(time.clone());
+    // Commands are ignored until the sensor is woken up
+    assert!(sensor.write(DEVICE_I2C_ADDR, &[0x03, 0x00, 0x04]).is_err());
+    time.advance_us(1000);
+    assert!(sensor.is_awake());
+    sensor.write(DEVICE_I2C_ADDR, &[0x03, 0x00, 0x04]).unwrap();
+    // The response isn't ready before the conversion time
+    assert!(sensor.read(DEVICE_I2C_ADDR, &mut [0; 8]).is_err());
+    time.advance_us(1500);
+    sensor.read(DEVICE_I2C_ADDR, &mut [0; 8]).unwrap();
+    time.advance_us(3001);
+    assert!(!sensor.is_awake());
+}
+
+#[test]
+fn test_faults() {
+    let time = SimTime::new();
+    let mut sensor = SimulatedAm2320::new(time.clone());
+    let mut am2320 = Am2320::new(&mut sensor, time.clone());
+    am2320.device.inject(Fault::CorruptCrc);
+    assert!(matches!(am2320.read_raw(), Err(Error::CrcMismatch { .. })));
+    am2320.device.inject(Fault::NackRead);
+    assert!(matches!(am2320.read_raw(), Err(Error::ReadError(_))));
+    am2320.device.inject(Fault::Exception(0x81));
+    assert_eq!(
+        am2320.read_raw(),
+        Err(Error::ModbusException(ModbusException::IllegalAddress))
+    );
+
+    time.advance_us(10_000);
+    sensor.inject(Fault::StayAsleep);
+    sensor.inject(Fault::BadHeader);
+    let mut am2320 = Am2320::new(&mut sensor, time).with_retry(RetryPolicy::new(3, 0));
+    am2320.read_raw().unwrap();
+    assert_eq!(am2320.last_attempts(), 3);
+}
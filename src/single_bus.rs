@@ -0,0 +1,242 @@
+//! Single-bus protocol, for boards without a free I2C controller
+//!
+//! With SCL tied to ground, the AM2320 answers on SDA alone using a
+//! proprietary one-wire style protocol:
+//!
+//! 1. the host pulls the line low for about 1ms, then releases it
+//! 2. the sensor pulls it low for 80µs, then high for 80µs
+//! 3. the sensor sends 40 bits, msb first, each one a 50µs low pulse
+//!    followed by a 26µs (0) or 70µs (1) high pulse
+//! 4. the sensor pulls the line low for 50µs and releases it
+//!
+//! The frame holds the humidity and the temperature, in the same format as
+//! registers 0x00 to 0x03, followed by a parity byte: the sum of these four
+//! bytes.
+//!
+//! Bits are told apart by comparing the length of their high pulse with the
+//! length of their low pulse, which only needs the delay to be roughly
+//! accurate. Interrupts lasting more than a few tens of microseconds while
+//! reading will still corrupt the frame, so disable them if you can.
+
+#[cfg(test)]
+extern crate std;
+
+use core::fmt;
+
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+};
+
+use crate::{protocol::decode_measurement, RangePolicy, RawMeasurement};
+
+/// How long the host pulls the line low to start a measurement
+const START_US: u32 = 1000;
+/// Longest pulse accepted from the sensor, with some margin over its 200µs
+/// maximum response time
+const MAX_PULSE_US: u32 = 250;
+
+/// Single-bus errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// Failed to drive or read the pin
+    Pin(E),
+    /// The sensor didn't answer or stopped in the middle of the frame
+    Timeout,
+    /// The parity byte doesn't match the data
+    ParityMismatch {
+        /// Parity computed from the data
+        expected: u8,
+        /// Parity sent by the sensor
+        actual: u8,
+    },
+    /// The measurement is outside of the datasheet limits, see `RangePolicy`
+    OutOfRange(RawMeasurement),
+}
+
+impl<E> Error<E> {
+    /// Returns `true` if retrying the measurement may succeed
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Error::Timeout | Error::ParityMismatch { .. })
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Pin(e) => write!(f, "failed to drive or read the pin: {:?}", e),
+            Error::Timeout => f.write_str("timed out waiting for the sensor"),
+            Error::ParityMismatch { expected, actual } => write!(
+                f,
+                "parity mismatch, expected {:#04x} but the sensor sent {:#04x}",
+                expected, actual
+            ),
+            Error::OutOfRange(measurement) => {
+                write!(f, "measurement out of range: {}", measurement)
+            }
+        }
+    }
+}
+
+impl<E: fmt::Debug> core::error::Error for Error<E> {}
+
+/// Sensor configuration in single-bus mode
+pub struct SingleBus<Pin, Delay> {
+    /// Open-drain pin connected to SDA, with a pull-up resistor
+    pin: Pin,
+    /// Delay device to time the pulses
+    delay: Delay,
+    /// What to do with measurements outside of the datasheet limits
+    range: RangePolicy,
+}
+
+impl<Pin, Delay, E> SingleBus<Pin, Delay>
+where
+    Pin: InputPin<Error = E> + OutputPin<Error = E>,
+    Delay: DelayNs,
+{
+    /// Create a AM2320 temperature sensor driver using the single-bus
+    /// protocol on `pin`.
+    ///
+    /// The pin has to be an open-drain output with a pull-up resistor, since
+    /// both the host and the sensor drive the line low.
+    pub fn new(pin: Pin, delay: Delay) -> Self {
+        Self {
+            pin,
+            delay,
+            range: RangePolicy::default(),
+        }
+    }
+
+    /// Applies `policy` to measurements outside of the datasheet limits
+    pub fn with_range_policy(mut self, policy: RangePolicy) -> Self {
+        self.range = policy;
+        self
+    }
+
+    /// Destroys the driver and returns the pin and the delay
+    pub fn release(self) -> (Pin, Delay) {
+        (self.pin, self.delay)
+    }
+
+    /// Reads one `Measurement` from the sensor
+    ///
+    /// Like on I2C, the sensor shouldn't be polled more than once every 2
+    /// seconds.
+    #[cfg(feature = "float")]
+    pub fn read(&mut self) -> Result<crate::Measurement, Error<E>> {
+        self.read_raw().map(crate::Measurement::from)
+    }
+
+    /// Reads one `RawMeasurement` from the sensor
+    ///
+    /// Same as `read`, without any floating-point arithmetic.
+    pub fn read_raw(&mut self) -> Result<RawMeasurement, Error<E>> {
+        let frame = self.read_frame()?;
+
+        let data = [frame[0], frame[1], frame[2], frame[3]];
+        let expected = data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        if expected != frame[4] {
+            return Err(Error::ParityMismatch {
+                expected,
+                actual: frame[4],
+            });
+        }
+
+        self.range
+            .check(decode_measurement(&data))
+            .map_err(Error::OutOfRange)
+    }
+
+    /// Sends the start signal and reads the 40-bit frame
+    fn read_frame(&mut self) -> Result<[u8; 5], Error<E>> {
+        self.pin.set_low().map_err(Error::Pin)?;
+        self.delay.delay_us(START_US);
+        self.pin.set_high().map_err(Error::Pin)?;
+
+        // Response: the sensor pulls the line low, then releases it
+        self.pulse(true)?;
+        self.pulse(false)?;
+        self.pulse(true)?;
+
+        let mut frame = [0; 5];
+        for byte in frame.iter_mut() {
+            for _ in 0..8 {
+                let low = self.pulse(false)?;
+                let high = self.pulse(true)?;
+                *byte = (*byte << 1) | u8::from(high > low);
+            }
+        }
+        Ok(frame)
+    }
+
+    /// Waits for the line to leave `high`, returns how long it took in µs
+    fn pulse(&mut self, high: bool) -> Result<u32, Error<E>> {
+        let mut elapsed = 0;
+        while self.pin.is_high().map_err(Error::Pin)? == high {
+            if elapsed >= MAX_PULSE_US {
+                return Err(Error::Timeout);
+            }
+            self.delay.delay_us(1);
+            elapsed += 1;
+        }
+        Ok(elapsed)
+    }
+}
+
+#[cfg(test)]
+fn waveform(frame: &[u8; 5]) -> std::vec::Vec<(bool, u64)> {
+    let mut waveform = std::vec![(true, 30), (false, 80), (true, 80)];
+    for byte in frame {
+        for bit in (0..8).rev() {
+            let high = if byte & (1 << bit) != 0 { 70 } else { 26 };
+            waveform.extend([(false, 50), (true, high)]);
+        }
+    }
+    waveform.push((false, 50));
+    waveform
+}
+
+#[test]
+fn test_read() {
+    use crate::mock;
+
+    let now = core::cell::Cell::new(0);
+    let pin = mock::Pin::new(&now, waveform(&[0x02, 0x36, 0x80, 0x65, 0x1d]));
+    let mut am2320 = SingleBus::new(pin, mock::Clock(&now));
+    assert_eq!(
+        am2320.read_raw(),
+        Ok(RawMeasurement {
+            humidity_permille: 566,
+            temperature_decicelsius: -101,
+        })
+    );
+}
+
+#[test]
+fn test_errors() {
+    use crate::mock;
+
+    let now = core::cell::Cell::new(0);
+    let pin = mock::Pin::new(&now, waveform(&[0x02, 0x36, 0x80, 0x65, 0x1e]));
+    let mut am2320 = SingleBus::new(pin, mock::Clock(&now));
+    assert_eq!(
+        am2320.read_raw(),
+        Err(Error::ParityMismatch {
+            expected: 0x1d,
+            actual: 0x1e,
+        })
+    );
+
+    // The line stays high if nothing answers
+    let pin = mock::Pin::new(&now, std::vec::Vec::new());
+    let mut am2320 = SingleBus::new(pin, mock::Clock(&now));
+    assert_eq!(am2320.read_raw(), Err(Error::Timeout));
+
+    // Frame truncated after 8 bits
+    let pin = mock::Pin::new(&now, waveform(&[0xff; 5])[..19].to_vec());
+    let mut am2320 = SingleBus::new(pin, mock::Clock(&now));
+    assert_eq!(am2320.read_raw(), Err(Error::Timeout));
+}
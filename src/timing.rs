@@ -0,0 +1,119 @@
+//! Delays used while talking to the sensor
+
+/// Delays waited after waking the sensor up and after sending a command
+///
+/// The datasheet requires waiting between 0.8ms and 3ms after the wake-up
+/// write, after which the sensor goes back to sleep, and at least 1.5ms for
+/// the sensor to answer a command. Clones and long buses may need more.
+///
+/// Deserializing goes through `Timing::new`, so it fails for delays out of
+/// these bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "TimingFields")
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Timing {
+    wake_us: u32,
+    conversion_us: u32,
+}
+
+impl Timing {
+    /// Shortest wait after the wake-up write allowed by the datasheet
+    pub const MIN_WAKE_US: u32 = 800;
+    /// Longest wait after the wake-up write allowed by the datasheet
+    pub const MAX_WAKE_US: u32 = 3000;
+    /// Shortest wait after a command allowed by the datasheet
+    pub const MIN_CONVERSION_US: u32 = 1500;
+
+    /// Creates a timing profile, returns `None` if the delays are out of the
+    /// datasheet bounds
+    pub const fn new(wake_us: u32, conversion_us: u32) -> Option<Self> {
+        if wake_us < Self::MIN_WAKE_US
+            || wake_us > Self::MAX_WAKE_US
+            || conversion_us < Self::MIN_CONVERSION_US
+        {
+            return None;
+        }
+        Some(Self {
+            wake_us,
+            conversion_us,
+        })
+    }
+
+    /// The minimum delays from the datasheet, 0.8ms and 1.5ms
+    pub const fn datasheet() -> Self {
+        Self {
+            wake_us: Self::MIN_WAKE_US,
+            conversion_us: Self::MIN_CONVERSION_US,
+        }
+    }
+
+    /// Generous delays for clones and long buses, 1.5ms and 3ms
+    pub const fn conservative() -> Self {
+        Self {
+            wake_us: 1500,
+            conversion_us: 3000,
+        }
+    }
+
+    /// Time to wait after waking the sensor up, in microseconds
+    pub const fn wake_us(&self) -> u32 {
+        self.wake_us
+    }
+
+    /// Time to wait after sending a command, in microseconds
+    pub const fn conversion_us(&self) -> u32 {
+        self.conversion_us
+    }
+}
+
+impl Default for Timing {
+    /// Slightly above the datasheet minimums, 0.9ms and 1.6ms
+    fn default() -> Self {
+        Self {
+            wake_us: 900,
+            conversion_us: 1600,
+        }
+    }
+}
+
+/// Unchecked fields of a deserialized `Timing`
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct TimingFields {
+    wake_us: u32,
+    conversion_us: u32,
+}
+
+#[cfg(feature = "serde")]
+impl core::convert::TryFrom<TimingFields> for Timing {
+    type Error = &'static str;
+
+    fn try_from(fields: TimingFields) -> Result<Self, Self::Error> {
+        Timing::new(fields.wake_us, fields.conversion_us)
+            .ok_or("delays out of the datasheet bounds")
+    }
+}
+
+#[test]
+fn test_bounds() {
+    assert_eq!(Timing::new(800, 1500), Some(Timing::datasheet()));
+    assert_eq!(Timing::new(799, 1500), None);
+    assert_eq!(Timing::new(3001, 1500), None);
+    assert_eq!(Timing::new(900, 1499), None);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde() {
+    let json = serde_json::to_string(&Timing::conservative()).unwrap();
+    assert_eq!(json, r#"{"wake_us":1500,"conversion_us":3000}"#);
+    assert_eq!(
+        serde_json::from_str::<Timing>(&json).unwrap(),
+        Timing::conservative()
+    );
+    assert!(serde_json::from_str::<Timing>(r#"{"wake_us":5000,"conversion_us":3000}"#).is_err());
+}
@@ -0,0 +1,86 @@
+//! Strongly-typed temperature and humidity
+
+/// Temperature, convertible to any unit
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Temperature(f32);
+
+impl Temperature {
+    /// Creates a temperature from degrees celsius (°C)
+    pub const fn from_celsius(celsius: f32) -> Self {
+        Self(celsius)
+    }
+
+    /// Temperature in degrees celsius (°C)
+    pub fn celsius(self) -> f32 {
+        self.0
+    }
+
+    /// Temperature in degrees fahrenheit (°F)
+    pub fn fahrenheit(self) -> f32 {
+        self.0 * 1.8 + 32.0
+    }
+
+    /// Temperature in kelvin (K)
+    pub fn kelvin(self) -> f32 {
+        self.0 + 273.15
+    }
+}
+
+/// Relative humidity
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RelativeHumidity(f32);
+
+impl RelativeHumidity {
+    /// Creates a relative humidity from percents (%)
+    pub const fn from_percent(percent: f32) -> Self {
+        Self(percent)
+    }
+
+    /// Relative humidity in percents (%)
+    pub fn percent(self) -> f32 {
+        self.0
+    }
+
+    /// Relative humidity as a ratio between 0 and 1
+    pub fn ratio(self) -> f32 {
+        self.0 / 100.0
+    }
+}
+
+#[cfg(feature = "uom")]
+impl From<Temperature> for uom::si::f32::ThermodynamicTemperature {
+    fn from(temperature: Temperature) -> Self {
+        Self::new::<uom::si::thermodynamic_temperature::degree_celsius>(temperature.celsius())
+    }
+}
+
+#[cfg(feature = "uom")]
+impl From<RelativeHumidity> for uom::si::f32::Ratio {
+    fn from(humidity: RelativeHumidity) -> Self {
+        Self::new::<uom::si::ratio::percent>(humidity.percent())
+    }
+}
+
+#[test]
+fn test_conversions() {
+    let temperature = Temperature::from_celsius(25.0);
+    assert_eq!(temperature.fahrenheit(), 77.0);
+    assert_eq!(temperature.kelvin(), 298.15);
+    assert_eq!(Temperature::from_celsius(-40.0).fahrenheit(), -40.0);
+    assert_eq!(RelativeHumidity::from_percent(50.0).ratio(), 0.5);
+}
+
+#[cfg(feature = "uom")]
+#[test]
+fn test_uom() {
+    use uom::si::{f32::*, ratio::ratio, thermodynamic_temperature::kelvin};
+
+    let temperature = ThermodynamicTemperature::from(Temperature::from_celsius(25.0));
+    assert_eq!(temperature.get::<kelvin>(), 298.15);
+    let humidity = Ratio::from(RelativeHumidity::from_percent(50.0));
+    assert_eq!(humidity.get::<ratio>(), 0.5);
+}